@@ -35,7 +35,7 @@ fn read_schematic(input: &str) -> Vec<Vec<char>> {
 }
 
 #[allow(dead_code)]
-fn print_schematic(schematic: &Vec<Vec<char>>) {
+fn print_schematic(schematic: &[Vec<char>]) {
     for row in schematic {
         for c in row {
             print!("{}", c);
@@ -88,44 +88,177 @@ impl Direction {
     }
 }
 
-fn is_adjacent_to_symbol(schematic: &[Vec<char>], row: usize, col: usize) -> bool {
-    fn is_symbol(c: char) -> bool {
-        c != '.' && !c.is_ascii_digit()
+// which cells count as neighbours of a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Neighborhood {
+    // the four orthogonal directions
+    VonNeumann,
+    // all eight compass directions
+    Moore,
+    // every cell within k rows and k columns
+    Radius(usize),
+}
+
+impl Neighborhood {
+    fn offsets(&self) -> Vec<(i32, i32)> {
+        match self {
+            Neighborhood::VonNeumann => [Direction::N, Direction::E, Direction::S, Direction::W]
+                .iter()
+                .map(Direction::offset)
+                .collect(),
+            Neighborhood::Moore => {
+                // Start above current position, N direction, and go clockwise until we are back at N.
+                let mut offsets = Vec::new();
+                let mut direction = Direction::N;
+                while direction != Direction::None {
+                    offsets.push(direction.offset());
+                    direction = direction.next();
+                }
+                offsets
+            }
+            Neighborhood::Radius(k) => {
+                let k = *k as i32;
+                (-k..=k)
+                    .flat_map(|row_offset| (-k..=k).map(move |col_offset| (row_offset, col_offset)))
+                    .filter(|&offset| offset != (0, 0))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Neighborhood {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "von-neumann" | "4" => Ok(Neighborhood::VonNeumann),
+            "moore" | "8" => Ok(Neighborhood::Moore),
+            _ => s
+                .strip_prefix("radius=")
+                .and_then(|k| k.parse().ok())
+                .map(Neighborhood::Radius)
+                .ok_or_else(|| format!("unknown neighborhood: {}", s)),
+        }
     }
+}
 
-    // Start looking above current position, N direction, and look clockwise until we are back at N.
-    let mut direction = Direction::N;
-    while direction != Direction::None {
-        let (row_offset, col_offset) = direction.offset();
-        let neighbor_row = row as i32 + row_offset;
-        let neighbor_col = col as i32 + col_offset;
+// how neighbours are found: the neighbourhood shape, and whether
+// the edges of the schematic wrap around (toroidal) or are walls
+#[derive(Debug, Clone, Copy)]
+struct Adjacency {
+    neighborhood: Neighborhood,
+    wrap: bool,
+}
 
-        if is_symbol(schematic[neighbor_row as usize][neighbor_col as usize]) {
-            return true;
+impl Default for Adjacency {
+    fn default() -> Self {
+        Adjacency {
+            neighborhood: Neighborhood::Moore,
+            wrap: false,
         }
+    }
+}
+
+impl Adjacency {
+    /// Returns the positions of the neighbours of (row, col) in the padded schematic.
+    /// Neighbours are always inside the original schematic, never in the margin.
+    fn neighbors(&self, schematic: &[Vec<char>], row: usize, col: usize) -> Vec<(usize, usize)> {
+        // size of the schematic without the margin
+        let height = schematic.len() as i32 - 2;
+        let width = schematic[0].len() as i32 - 2;
 
-        direction = direction.next();
+        let mut neighbors = Vec::new();
+        for (row_offset, col_offset) in self.neighborhood.offsets() {
+            let mut neighbor_row = row as i32 - 1 + row_offset;
+            let mut neighbor_col = col as i32 - 1 + col_offset;
+
+            if self.wrap {
+                neighbor_row = neighbor_row.rem_euclid(height);
+                neighbor_col = neighbor_col.rem_euclid(width);
+            } else if !(0..height).contains(&neighbor_row) || !(0..width).contains(&neighbor_col) {
+                continue;
+            }
+
+            neighbors.push((neighbor_row as usize + 1, neighbor_col as usize + 1));
+        }
+        neighbors
     }
-    false
 }
 
-fn find_part_numbers(schematic: &Vec<Vec<char>>) -> Vec<u32> {
+// how numbers and symbols are read out of the schematic
+#[derive(Debug, Clone)]
+struct Tokenizer {
+    // a '-' directly before digits is the sign of a negative number, not a symbol
+    signed: bool,
+    // characters that separate numbers without being symbols
+    separators: Vec<char>,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Tokenizer {
+            signed: false,
+            separators: vec!['.'],
+        }
+    }
+}
+
+impl Tokenizer {
+    /// Returns true if the char at (row, col) is the minus sign at the start of a number.
+    fn is_sign(&self, schematic: &[Vec<char>], row: usize, col: usize) -> bool {
+        // the margin guarantees there is always a char to the left and right
+        self.signed
+            && schematic[row][col] == '-'
+            && !schematic[row][col - 1].is_ascii_digit()
+            && schematic[row][col + 1].is_ascii_digit()
+    }
+
+    /// Returns true if the char at (row, col) is part of a number.
+    fn is_number(&self, schematic: &[Vec<char>], row: usize, col: usize) -> bool {
+        schematic[row][col].is_ascii_digit() || self.is_sign(schematic, row, col)
+    }
+
+    fn is_symbol(&self, schematic: &[Vec<char>], row: usize, col: usize) -> bool {
+        !self.separators.contains(&schematic[row][col]) && !self.is_number(schematic, row, col)
+    }
+}
+
+fn is_adjacent_to_symbol(
+    schematic: &[Vec<char>],
+    adjacency: &Adjacency,
+    tokenizer: &Tokenizer,
+    row: usize,
+    col: usize,
+) -> bool {
+    adjacency
+        .neighbors(schematic, row, col)
+        .into_iter()
+        .any(|(neighbor_row, neighbor_col)| {
+            tokenizer.is_symbol(schematic, neighbor_row, neighbor_col)
+        })
+}
+
+fn find_part_numbers(
+    schematic: &[Vec<char>],
+    adjacency: &Adjacency,
+    tokenizer: &Tokenizer,
+) -> Vec<i64> {
     let mut part_nums = Vec::new();
     let mut num_buf = String::new();
     let mut is_part_number = false;
 
     for row in 0..schematic.len() {
         for col in 0..schematic[row].len() {
-            let cur_char = schematic[row][col];
-            if cur_char.is_ascii_digit() {
+            if tokenizer.is_number(schematic, row, col) {
                 num_buf.push(schematic[row][col]);
-                is_part_number |= is_adjacent_to_symbol(schematic, row, col);
+                is_part_number |= is_adjacent_to_symbol(schematic, adjacency, tokenizer, row, col);
             } else {
                 // we are at the end of a number.
                 // if it's a part number, we'll add it to the list
                 // and then go looking for the next number
                 if is_part_number {
-                    part_nums.push(num_buf.parse::<u32>().unwrap());
+                    part_nums.push(num_buf.parse::<i64>().unwrap());
                 }
                 num_buf.clear();
                 is_part_number = false;
@@ -135,39 +268,42 @@ fn find_part_numbers(schematic: &Vec<Vec<char>>) -> Vec<u32> {
     part_nums
 }
 
-fn part_1() {
+fn part_1(adjacency: &Adjacency, tokenizer: &Tokenizer) {
     let input = include_str!("my_input.txt");
     let schematic = &read_schematic(input);
 
-    let sum: u32 = find_part_numbers(schematic).iter().sum();
+    let sum: i64 = find_part_numbers(schematic, adjacency, tokenizer)
+        .iter()
+        .sum();
     println!("Part 1 Sum: {}", sum);
 }
 
-fn find_adjacent_gear_symbol(
+/// Returns every gear next to (row, col). A number can touch more than one gear, and then
+/// it's a part number of each of them.
+fn find_adjacent_gear_symbols(
     schematic: &[Vec<char>],
+    adjacency: &Adjacency,
     row: usize,
     col: usize,
-) -> Option<(usize, usize)> {
+) -> Vec<(usize, usize)> {
     fn is_gear_symbol(c: char) -> bool {
         c == '*'
     }
 
-    let mut direction = Direction::N;
-    while direction != Direction::None {
-        let (row_offset, col_offset) = direction.offset();
-        let neighbor_row = (row as i32 + row_offset) as usize;
-        let neighbor_col = (col as i32 + col_offset) as usize;
-
-        if is_gear_symbol(schematic[neighbor_row][neighbor_col]) {
-            return Some((neighbor_row, neighbor_col));
-        }
-
-        direction = direction.next();
-    }
-    None
+    adjacency
+        .neighbors(schematic, row, col)
+        .into_iter()
+        .filter(|&(neighbor_row, neighbor_col)| {
+            is_gear_symbol(schematic[neighbor_row][neighbor_col])
+        })
+        .collect()
 }
 
-fn find_gear_part_nums(schematic: &Vec<Vec<char>>) -> Vec<(u32, u32)> {
+fn find_gear_part_nums(
+    schematic: &[Vec<char>],
+    adjacency: &Adjacency,
+    tokenizer: &Tokenizer,
+) -> Vec<(i64, i64)> {
     let mut gear_part_nums = Vec::new();
     let mut gear_loc_to_part_nums = HashMap::<(usize, usize), Vec<_>>::new();
 
@@ -178,19 +314,16 @@ fn find_gear_part_nums(schematic: &Vec<Vec<char>>) -> Vec<(u32, u32)> {
 
     for row in 0..schematic.len() {
         for col in 0..schematic[row].len() {
-            let cur_char = schematic[row][col];
-            if cur_char.is_ascii_digit() {
+            if tokenizer.is_number(schematic, row, col) {
                 num_buf.push(schematic[row][col]);
-                is_part_number |= is_adjacent_to_symbol(schematic, row, col);
-                if let Some(gear_loc) = find_adjacent_gear_symbol(schematic, row, col) {
-                    gear_locs.insert(gear_loc);
-                }
+                is_part_number |= is_adjacent_to_symbol(schematic, adjacency, tokenizer, row, col);
+                gear_locs.extend(find_adjacent_gear_symbols(schematic, adjacency, row, col));
             } else {
                 // we are at the end of a number.
                 // if it's a gear, we'll add it to the list
                 // and then go looking for the next number
                 if is_part_number && !gear_locs.is_empty() {
-                    let part_num = num_buf.parse::<u32>().unwrap();
+                    let part_num = num_buf.parse::<i64>().unwrap();
                     gear_locs.iter().for_each(|loc| {
                         gear_loc_to_part_nums
                             .entry(*loc)
@@ -215,20 +348,214 @@ fn find_gear_part_nums(schematic: &Vec<Vec<char>>) -> Vec<(u32, u32)> {
     gear_part_nums
 }
 
-fn part_2() {
+fn sum_gear_ratios(gear_part_nums: &[(i64, i64)]) -> i64 {
+    gear_part_nums.iter().map(|(a, b)| a * b).sum()
+}
+
+fn part_2(adjacency: &Adjacency, tokenizer: &Tokenizer) {
     let input = include_str!("my_input.txt");
     let schematic = read_schematic(input);
 
-    let gear_part_nums = find_gear_part_nums(&schematic);
-    let gear_ratios = gear_part_nums
-        .iter()
-        .map(|(a, b)| (*a as u64) * (*b as u64));
+    let gear_part_nums = find_gear_part_nums(&schematic, adjacency, tokenizer);
 
-    let sum = gear_ratios.sum::<u64>();
+    let sum = sum_gear_ratios(&gear_part_nums);
     println!("Part 2 Sum: {}", sum);
 }
 
+// usage: day03 [--neighborhood von-neumann|moore|radius=K] [--wrap]
+//              [--signed] [--separators CHARS]
 fn main() {
-    part_1();
-    part_2();
+    let mut adjacency = Adjacency::default();
+    let mut tokenizer = Tokenizer::default();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--neighborhood" => {
+                let neighborhood = args
+                    .next()
+                    .expect("Should have a neighborhood after --neighborhood");
+                adjacency.neighborhood = neighborhood.parse().unwrap();
+            }
+            "--wrap" => adjacency.wrap = true,
+            "--signed" => tokenizer.signed = true,
+            "--separators" => {
+                let separators = args
+                    .next()
+                    .expect("Should have separator chars after --separators");
+                tokenizer.separators = separators.chars().collect();
+            }
+            _ => panic!("unknown argument: {}", arg),
+        }
+    }
+
+    part_1(&adjacency, &tokenizer);
+    part_2(&adjacency, &tokenizer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("input.txt");
+
+    fn solve(input: &str, adjacency: &Adjacency, tokenizer: &Tokenizer) -> (i64, i64) {
+        let schematic = read_schematic(input);
+        let part_1 = find_part_numbers(&schematic, adjacency, tokenizer)
+            .iter()
+            .sum();
+        let part_2 = sum_gear_ratios(&find_gear_part_nums(&schematic, adjacency, tokenizer));
+        (part_1, part_2)
+    }
+
+    fn adjacency(neighborhood: &str, wrap: bool) -> Adjacency {
+        Adjacency {
+            neighborhood: neighborhood.parse().unwrap(),
+            wrap,
+        }
+    }
+
+    #[test]
+    fn moore_solves_sample() {
+        let tokenizer = Tokenizer::default();
+        assert_eq!(
+            solve(SAMPLE, &adjacency("moore", false), &tokenizer),
+            (4361, 467835)
+        );
+        assert_eq!(
+            solve(SAMPLE, &adjacency("8", false), &tokenizer),
+            (4361, 467835)
+        );
+    }
+
+    #[test]
+    fn radius_one_is_moore() {
+        let mut moore = Neighborhood::Moore.offsets();
+        let mut radius = Neighborhood::Radius(1).offsets();
+        moore.sort();
+        radius.sort();
+        assert_eq!(moore, radius);
+
+        let tokenizer = Tokenizer::default();
+        assert_eq!(
+            solve(SAMPLE, &adjacency("radius=1", false), &tokenizer),
+            (4361, 467835)
+        );
+    }
+
+    #[test]
+    fn von_neumann_only_looks_orthogonally() {
+        // 467 and 592 only touch symbols diagonally, and so does 755 to its gear,
+        // which leaves every gear with one part number
+        let tokenizer = Tokenizer::default();
+        assert_eq!(
+            solve(SAMPLE, &adjacency("von-neumann", false), &tokenizer),
+            (35 + 633 + 617 + 664 + 598, 0)
+        );
+        assert_eq!(Neighborhood::VonNeumann.offsets().len(), 4);
+    }
+
+    #[test]
+    fn radius_two_reaches_every_number() {
+        let tokenizer = Tokenizer::default();
+        let all = 467 + 114 + 35 + 633 + 617 + 58 + 592 + 755 + 664 + 598;
+        assert_eq!(
+            solve(SAMPLE, &adjacency("radius=2", false), &tokenizer).0,
+            all
+        );
+        assert_eq!(Neighborhood::Radius(2).offsets().len(), 24);
+    }
+
+    #[test]
+    fn wrap_joins_opposite_edges() {
+        // the 3 reaches the gear around the right edge, and the 7 around the bottom
+        let input = "*...3\n.....\n7....";
+        let tokenizer = Tokenizer::default();
+        assert_eq!(solve(input, &adjacency("moore", false), &tokenizer), (0, 0));
+        assert_eq!(
+            solve(input, &adjacency("moore", true), &tokenizer),
+            (10, 21)
+        );
+
+        // nothing in the sample touches across an edge
+        assert_eq!(
+            solve(SAMPLE, &adjacency("moore", true), &tokenizer),
+            (4361, 467835)
+        );
+    }
+
+    #[test]
+    fn number_between_two_gears_counts_for_both() {
+        let tokenizer = Tokenizer::default();
+        assert_eq!(
+            solve("2*5*3", &adjacency("moore", false), &tokenizer),
+            (10, 2 * 5 + 5 * 3)
+        );
+    }
+
+    #[test]
+    fn unknown_neighborhood_is_an_error() {
+        assert!("hexagonal".parse::<Neighborhood>().is_err());
+        assert!("radius=".parse::<Neighborhood>().is_err());
+    }
+
+    fn signed() -> Tokenizer {
+        Tokenizer {
+            signed: true,
+            ..Tokenizer::default()
+        }
+    }
+
+    #[test]
+    fn signed_sample_is_unchanged() {
+        // the sample has no minus signs, so signed numbers change nothing
+        assert_eq!(
+            solve(SAMPLE, &Adjacency::default(), &signed()),
+            (4361, 467835)
+        );
+    }
+
+    #[test]
+    fn minus_before_a_number() {
+        let negative = SAMPLE.replace("..35..633.", ".-35..633.");
+
+        // a minus before 35 is a symbol by default, and 35 is already a part number
+        assert_eq!(
+            solve(&negative, &Adjacency::default(), &Tokenizer::default()),
+            (4361, 467835)
+        );
+        // with signed numbers it's -35 instead, which also makes the 467 * 35 gear negative
+        assert_eq!(
+            solve(&negative, &Adjacency::default(), &signed()),
+            (4361 - 2 * 35, 467835 - 2 * 467 * 35)
+        );
+    }
+
+    #[test]
+    fn minus_between_digits_is_a_symbol() {
+        let subtraction = SAMPLE.replace("..35..633.", "..35-633..");
+        assert_eq!(
+            solve(&subtraction, &Adjacency::default(), &signed()),
+            (4361, 467835)
+        );
+    }
+
+    #[test]
+    fn custom_separators() {
+        let underscored = SAMPLE.replace('.', "_");
+        let underscores = Tokenizer {
+            separators: vec!['_'],
+            ..Tokenizer::default()
+        };
+        assert_eq!(
+            solve(&underscored, &Adjacency::default(), &underscores),
+            (4361, 467835)
+        );
+
+        // the underscores become symbols unless we say otherwise
+        assert_eq!(
+            solve(&underscored, &Adjacency::default(), &Tokenizer::default()).0,
+            4533
+        );
+    }
 }
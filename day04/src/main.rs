@@ -1,76 +1,648 @@
+use std::iter::zip;
+
+#[derive(Debug)]
 struct Card {
-    wins: u8,
+    id: u32,
+    // both lists are kept sorted so we can binary search them
+    winning_nums: Vec<u32>,
+    scratched: Vec<u32>,
+    // the scratched numbers that are also winning numbers
+    matched: Vec<u32>,
+}
+
+impl Card {
+    fn wins(&self) -> usize {
+        self.matched.len()
+    }
+}
+
+fn parse_card(line: &str) -> Card {
+    // Split off the card number, then split on the winning numbers and the scratch numbers
+    let (id, line) = line.split_once(": ").unwrap();
+    let (winning_nums, scratched) = line.split_once(" | ").unwrap();
+
+    let id = id
+        .strip_prefix("Card")
+        .expect("Should have found a card number")
+        .trim()
+        .parse()
+        .unwrap();
+
+    let parse = |s: &str| {
+        let mut nums = s
+            .split_whitespace()
+            .map(|x| x.parse().unwrap())
+            .collect::<Vec<u32>>();
+        nums.sort_unstable();
+        nums
+    };
+
+    let winning_nums = parse(winning_nums);
+    let scratched = parse(scratched);
+
+    let matched = scratched
+        .iter()
+        .copied()
+        .filter(|matching_num| winning_nums.binary_search(matching_num).is_ok())
+        .collect();
+
+    Card {
+        id,
+        winning_nums,
+        scratched,
+        matched,
+    }
 }
 
 fn parse(input: &str) -> Vec<Card> {
-    let mut cards = Vec::new();
+    input.lines().map(parse_card).collect()
+}
 
-    for line in input.lines() {
-        // Split the line and throw away the card number, then split on the winning numbers and the scratch numbers
-        let (_, line) = line.split_once(": ").unwrap();
-        let (winning_nums, scratched) = line.split_once(" | ").unwrap();
+// how many points a card is worth for its number of wins
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PointsRule {
+    // 1 point for the first win, then doubled for each win after that
+    Doubling,
+    // 1 point per win
+    Linear,
+    // 1, 2, 3, 5, 8, ... points, each worth the sum of the two before it
+    Fibonacci,
+}
 
-        let parse = |s: &str| {
-            s.split_whitespace()
-                .map(|x| x.parse().unwrap())
-                .collect::<Vec<u8>>()
-        };
+impl PointsRule {
+    /// Returns the points for a card with `wins` wins, or `None` if they don't fit in a u64.
+    fn points(&self, wins: usize) -> Option<u64> {
+        if wins == 0 {
+            return Some(0);
+        }
+
+        match self {
+            PointsRule::Doubling => u32::try_from(wins - 1)
+                .ok()
+                .and_then(|doublings| 2u64.checked_pow(doublings)),
+            PointsRule::Linear => u64::try_from(wins).ok(),
+            PointsRule::Fibonacci => {
+                let (mut a, mut b) = (1u64, 1u64);
+                for _ in 1..wins {
+                    (a, b) = (b, a.checked_add(b)?);
+                }
+                Some(b)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for PointsRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "doubling" => Ok(PointsRule::Doubling),
+            "linear" => Ok(PointsRule::Linear),
+            "fibonacci" => Ok(PointsRule::Fibonacci),
+            _ => Err(format!("unknown points rule: {}", s)),
+        }
+    }
+}
+
+// which cards a card wins copies of, one copy of each of the next cards per win
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CopiesRule {
+    // cards past the end of the table are not won
+    Capped,
+    // cards past the end of the table wrap around to the start. Those cards
+    // have already been played, so their extra copies don't win anything more.
+    Wrapping,
+}
+
+impl CopiesRule {
+    /// Returns the indices of the cards that card `i` wins a copy of.
+    fn won_cards(&self, i: usize, wins: usize, num_cards: usize) -> Vec<usize> {
+        match self {
+            CopiesRule::Capped => ((i + 1)..(i + 1 + wins).min(num_cards)).collect(),
+            CopiesRule::Wrapping => (1..=wins).map(|j| (i + j) % num_cards).collect(),
+        }
+    }
+}
 
-        let winning_nums = parse(winning_nums);
-        let scratched = parse(scratched);
+impl std::str::FromStr for CopiesRule {
+    type Err = String;
 
-        cards.push(Card {
-            wins: scratched
-                .iter()
-                .filter(|matching_num| winning_nums.contains(matching_num))
-                .count() as u8,
-        });
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "capped" => Ok(CopiesRule::Capped),
+            "wrapping" => Ok(CopiesRule::Wrapping),
+            _ => Err(format!("unknown copies rule: {}", s)),
+        }
     }
-    cards
 }
 
-fn part_1() {
+fn part_1(rule: PointsRule) {
     let input = include_str!("my_input.txt");
 
     let cards = parse(input);
 
-    // How else can I do this without using pow?
-    let sum = cards
-        .iter()
-        .filter(|c| c.wins > 0)
-        .map(|c| 2u32.pow(c.wins.saturating_sub(1) as u32))
-        .sum::<u32>();
+    let sum = total_points(&cards, rule).unwrap_or_else(|err| refuse(err));
 
     println!("Part 1 Sum: {}", sum);
 }
 
-fn part_2() {
+// the doubling and fibonacci points grow quickly with the wins, so they can outgrow a u64
+#[derive(Debug, PartialEq, Eq)]
+struct PointsOverflow {
+    // the card worth too many points, or the last card counted towards too many in total
+    card: u32,
+}
+
+impl std::fmt::Display for PointsOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "too many points on card {} to count", self.card)
+    }
+}
+
+/// Returns the points for all the cards together.
+fn total_points(cards: &[Card], rule: PointsRule) -> Result<u64, PointsOverflow> {
+    cards.iter().try_fold(0u64, |total, card| {
+        rule.points(card.wins())
+            .and_then(|points| total.checked_add(points))
+            .ok_or(PointsOverflow { card: card.id })
+    })
+}
+
+// how many copies of each card we end up with, and where they came from
+struct CopyCascade {
+    // copies of each card, counting the original
+    copies: Vec<usize>,
+    // for each card, the earlier cards that won copies of it and how many they won
+    sources: Vec<Vec<(usize, usize)>>,
+}
+
+// the copies double with every card that wins them all, so they can outgrow a usize
+#[derive(Debug, PartialEq, Eq)]
+struct CopiesOverflow {
+    // the card we had too many copies of, or the last card counted towards too many in total
+    card: u32,
+}
+
+impl std::fmt::Display for CopiesOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "too many copies of card {} to count", self.card)
+    }
+}
+
+/// Plays out the copies won by every card.
+/// Each card's copies are all final by the time we reach it, since only earlier cards
+/// can win copies of it, so one pass over the cards is enough.
+fn copy_cascade(cards: &[Card], rule: CopiesRule) -> Result<CopyCascade, CopiesOverflow> {
+    let mut copies = vec![1usize; cards.len()];
+    let mut sources = vec![Vec::new(); cards.len()];
+
+    for (i, card) in cards.iter().enumerate() {
+        // Every copy of this card wins one more copy of each of the won cards. When the
+        // copies wrap around, this card can win copies of itself, and those mustn't win
+        // anything on this pass, so count the copies before handing out any.
+        let winning_copies = copies[i];
+        for j in rule.won_cards(i, card.wins(), cards.len()) {
+            copies[j] = copies[j]
+                .checked_add(winning_copies)
+                .ok_or(CopiesOverflow { card: cards[j].id })?;
+            sources[j].push((i, winning_copies));
+        }
+    }
+    Ok(CopyCascade { copies, sources })
+}
+
+/// Returns how many copies of each card we end up with, counting the original.
+fn count_copies(cards: &[Card], rule: CopiesRule) -> Result<Vec<usize>, CopiesOverflow> {
+    Ok(copy_cascade(cards, rule)?.copies)
+}
+
+/// Returns how many cards we end up with altogether, counting the originals.
+fn total_copies(cards: &[Card], rule: CopiesRule) -> Result<usize, CopiesOverflow> {
+    zip(cards, count_copies(cards, rule)?).try_fold(0usize, |total, (card, copies)| {
+        total
+            .checked_add(copies)
+            .ok_or(CopiesOverflow { card: card.id })
+    })
+}
+
+/// Prints the error and exits, rather than scoring with a count that wrapped around.
+fn refuse(err: impl std::fmt::Display) -> ! {
+    eprintln!("{}", err);
+    std::process::exit(1);
+}
+
+fn print_cascade_table(cards: &[Card], cascade: &CopyCascade) {
+    println!("{:>8} {:>10}  Won From", "Card", "Copies");
+    for (i, card) in cards.iter().enumerate() {
+        let won_from = cascade.sources[i]
+            .iter()
+            .map(|&(source, copies)| format!("Card {} x{}", cards[source].id, copies))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("{:>8} {:>10}  {}", card.id, cascade.copies[i], won_from);
+    }
+}
+
+fn print_cascade_dot(cards: &[Card], cascade: &CopyCascade) {
+    println!("digraph cascade {{");
+    for (i, card) in cards.iter().enumerate() {
+        println!(
+            "    card{} [label=\"Card {}\\n{} copies\"];",
+            card.id, card.id, cascade.copies[i]
+        );
+    }
+    for (i, card) in cards.iter().enumerate() {
+        for &(source, copies) in &cascade.sources[i] {
+            println!(
+                "    card{} -> card{} [label=\"{}\"];",
+                cards[source].id, card.id, copies
+            );
+        }
+    }
+    println!("}}");
+}
+
+fn explain(rule: CopiesRule, format: &str) {
+    let input = include_str!("my_input.txt");
+    let cards = parse(input);
+
+    let cascade = copy_cascade(&cards, rule).unwrap_or_else(|err| refuse(err));
+    match format {
+        "table" => print_cascade_table(&cards, &cascade),
+        "dot" => print_cascade_dot(&cards, &cascade),
+        _ => panic!("unknown explain format: {}", format),
+    }
+}
+
+fn part_2(rule: CopiesRule) {
     let input = include_str!("my_input.txt");
     let cards = parse(input);
 
-    let mut queue = (0..cards.len()).collect::<Vec<_>>();
-    let mut visited = 0;
+    let visited = total_copies(&cards, rule).unwrap_or_else(|err| refuse(err));
+
+    println!("Part 2 Visited: {}", visited);
+}
+
+// something odd about a card table that would make its score suspect
+#[derive(Debug, PartialEq, Eq)]
+enum Anomaly {
+    DuplicateWinningNum(u32),
+    DuplicateScratchedNum(u32),
+    // (winning, scratched) list lengths that differ from the first card's
+    ListLengths {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+    NonSequentialId {
+        expected: u32,
+        found: u32,
+    },
+    // a card after the one with the biggest id there can be
+    IdAfterLast {
+        found: u32,
+    },
+}
+
+impl std::fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Anomaly::DuplicateWinningNum(num) => write!(f, "duplicate winning number {}", num),
+            Anomaly::DuplicateScratchedNum(num) => {
+                write!(f, "duplicate scratched number {}", num)
+            }
+            Anomaly::ListLengths { expected, found } => write!(
+                f,
+                "expected {} winning and {} scratched numbers, found {} and {}",
+                expected.0, expected.1, found.0, found.1
+            ),
+            Anomaly::NonSequentialId { expected, found } => {
+                write!(f, "expected card {}, found card {}", expected, found)
+            }
+            Anomaly::IdAfterLast { found } => {
+                write!(f, "found card {} after card {}", found, u32::MAX)
+            }
+        }
+    }
+}
+
+/// Returns the anomalies in the card table along with their line numbers.
+/// Each card is on its own line, so the line number is the card's index plus one.
+fn validate(cards: &[Card]) -> Vec<(usize, Anomaly)> {
+    fn duplicates(nums: &[u32]) -> impl Iterator<Item = u32> + '_ {
+        // the lists are sorted, so duplicates are next to each other. A number that's
+        // there three or more times is still only reported once.
+        nums.chunk_by(|a, b| a == b)
+            .filter(|run| run.len() > 1)
+            .map(|run| run[0])
+    }
+
+    let mut anomalies = Vec::new();
+    let Some(first) = cards.first() else {
+        return anomalies;
+    };
+    let expected_lengths = (first.winning_nums.len(), first.scratched.len());
 
-    while let Some(i) = queue.pop() {
-        // Keep track of how many cards we've processed
-        visited += 1;
+    for (i, card) in cards.iter().enumerate() {
+        let line = i + 1;
 
-        let card = &cards[i];
-        // Only add the next card if it has a winning number
-        if card.wins == 0 {
-            continue;
+        for num in duplicates(&card.winning_nums) {
+            anomalies.push((line, Anomaly::DuplicateWinningNum(num)));
+        }
+        for num in duplicates(&card.scratched) {
+            anomalies.push((line, Anomaly::DuplicateScratchedNum(num)));
+        }
+
+        let lengths = (card.winning_nums.len(), card.scratched.len());
+        if lengths != expected_lengths {
+            anomalies.push((
+                line,
+                Anomaly::ListLengths {
+                    expected: expected_lengths,
+                    found: lengths,
+                },
+            ));
         }
 
-        // Add a new card for each winning number
-        for j in 0..card.wins as usize {
-            queue.push(j + i + 1);
+        // the first card is card 1, and the others compare with the card before, so one
+        // missing card is only reported once
+        let expected_id = match i.checked_sub(1) {
+            None => Some(1),
+            Some(previous) => cards[previous].id.checked_add(1),
+        };
+        match expected_id {
+            Some(expected) if card.id != expected => anomalies.push((
+                line,
+                Anomaly::NonSequentialId {
+                    expected,
+                    found: card.id,
+                },
+            )),
+            Some(_) => {}
+            None => anomalies.push((line, Anomaly::IdAfterLast { found: card.id })),
         }
     }
+    anomalies
+}
 
-    println!("Part 2 Visited: {}", visited);
+/// Reports any anomalies in the card table.
+/// In strict mode we refuse to score a table with anomalies.
+fn check_cards(strict: bool) {
+    let input = include_str!("my_input.txt");
+    let cards = parse(input);
+
+    let anomalies = validate(&cards);
+    for (line, anomaly) in &anomalies {
+        eprintln!("line {}: {}", line, anomaly);
+    }
+
+    if strict && !anomalies.is_empty() {
+        eprintln!(
+            "Refusing to score a card table with {} anomalies",
+            anomalies.len()
+        );
+        std::process::exit(1);
+    }
 }
 
+// usage: day04 [--points doubling|linear|fibonacci] [--copies capped|wrapping]
+//              [--explain table|dot] [--strict]
 fn main() {
-    part_1();
-    part_2();
+    let mut points_rule = PointsRule::Doubling;
+    let mut copies_rule = CopiesRule::Capped;
+    let mut explain_format = None;
+    let mut strict = false;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--points" => {
+                let rule = args.next().expect("Should have a rule after --points");
+                points_rule = rule.parse().unwrap();
+            }
+            "--copies" => {
+                let rule = args.next().expect("Should have a rule after --copies");
+                copies_rule = rule.parse().unwrap();
+            }
+            "--explain" => {
+                let format = args.next().expect("Should have a format after --explain");
+                explain_format = Some(format);
+            }
+            "--strict" => strict = true,
+            _ => panic!("unknown argument: {}", arg),
+        }
+    }
+
+    check_cards(strict);
+
+    if let Some(format) = explain_format {
+        return explain(copies_rule, &format);
+    }
+
+    part_1(points_rule);
+    part_2(copies_rule);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("input.txt");
+
+    /// A card that matches `wins` numbers, for tables the puzzle wouldn't give us.
+    fn card_with_wins(id: u32, wins: u32) -> Card {
+        let nums = (1..=wins).collect::<Vec<_>>();
+        Card {
+            id,
+            winning_nums: nums.clone(),
+            scratched: nums.clone(),
+            matched: nums,
+        }
+    }
+
+    #[test]
+    fn sample_copies() {
+        let cards = parse(SAMPLE);
+        assert_eq!(
+            count_copies(&cards, CopiesRule::Capped),
+            Ok(vec![1, 2, 4, 8, 14, 1])
+        );
+        assert_eq!(total_copies(&cards, CopiesRule::Capped), Ok(30));
+    }
+
+    #[test]
+    fn copies_overflow_is_an_error() {
+        // each card wins a copy of every card after it, so the copies double every card
+        let count = usize::BITS;
+        let cards = (0..count)
+            .map(|i| card_with_wins(i + 1, count - i - 1))
+            .collect::<Vec<_>>();
+
+        // the last card has 2^(count - 1) copies, and all of them add up to usize::MAX,
+        // so everything only just fits
+        let copies = count_copies(&cards, CopiesRule::Capped).unwrap();
+        assert_eq!(copies[count as usize - 1], 1 << (count - 1));
+        assert_eq!(total_copies(&cards, CopiesRule::Capped), Ok(usize::MAX));
+
+        // one more original card is one too many
+        let mut more = cards;
+        more.push(card_with_wins(count + 1, 0));
+        assert_eq!(
+            total_copies(&more, CopiesRule::Capped),
+            Err(CopiesOverflow { card: count + 1 })
+        );
+
+        // and one more card that wins them all doubles the copies one time too many
+        let cards = (0..=count)
+            .map(|i| card_with_wins(i + 1, count - i))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            count_copies(&cards, CopiesRule::Capped),
+            Err(CopiesOverflow { card: count + 1 })
+        );
+    }
+
+    #[test]
+    fn sample_points() {
+        let cards = parse(SAMPLE);
+        assert_eq!(total_points(&cards, PointsRule::Doubling), Ok(13));
+        assert_eq!(total_points(&cards, PointsRule::Linear), Ok(4 + 2 + 2 + 1));
+        assert_eq!(
+            total_points(&cards, PointsRule::Fibonacci),
+            Ok(5 + 2 + 2 + 1)
+        );
+    }
+
+    #[test]
+    fn points_rules() {
+        let points = |rule: PointsRule| {
+            (0..=6)
+                .map(|wins| rule.points(wins).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(points(PointsRule::Doubling), [0, 1, 2, 4, 8, 16, 32]);
+        assert_eq!(points(PointsRule::Linear), [0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(points(PointsRule::Fibonacci), [0, 1, 2, 3, 5, 8, 13]);
+    }
+
+    #[test]
+    fn points_overflow_is_an_error() {
+        // 2^63 is the most a u64 can double to, and the 93rd fibonacci number the
+        // biggest that fits
+        assert_eq!(PointsRule::Doubling.points(64), Some(1 << 63));
+        assert_eq!(PointsRule::Doubling.points(65), None);
+        assert_eq!(PointsRule::Fibonacci.points(92), Some(12200160415121876738));
+        assert_eq!(PointsRule::Fibonacci.points(93), None);
+
+        let cards = [card_with_wins(1, 65)];
+        assert_eq!(
+            total_points(&cards, PointsRule::Doubling),
+            Err(PointsOverflow { card: 1 })
+        );
+
+        // each card's points fit, but not both together
+        let cards = [card_with_wins(1, 64), card_with_wins(2, 64)];
+        assert_eq!(
+            total_points(&cards, PointsRule::Doubling),
+            Err(PointsOverflow { card: 2 })
+        );
+        assert_eq!(total_points(&cards, PointsRule::Linear), Ok(128));
+    }
+
+    #[test]
+    fn wrapping_copies() {
+        // nothing in the sample wins past the last card, so wrapping changes nothing
+        let cards = parse(SAMPLE);
+        assert_eq!(total_copies(&cards, CopiesRule::Wrapping), Ok(30));
+
+        assert_eq!(CopiesRule::Capped.won_cards(3, 4, 5), [4]);
+        assert_eq!(CopiesRule::Wrapping.won_cards(3, 4, 5), [4, 0, 1, 2]);
+
+        // card 2 wins a copy of card 1, whose extra copy has already been played
+        let cards = [card_with_wins(1, 0), card_with_wins(2, 1)];
+        assert_eq!(count_copies(&cards, CopiesRule::Capped), Ok(vec![1, 1]));
+        assert_eq!(count_copies(&cards, CopiesRule::Wrapping), Ok(vec![2, 1]));
+    }
+
+    #[test]
+    fn wrapping_onto_itself() {
+        // card 1 wins copies of card 2, itself, then card 2 again. Its own new copy
+        // mustn't count when it wins the second copy of card 2.
+        let cards = [card_with_wins(1, 3), card_with_wins(2, 0)];
+        let cascade = copy_cascade(&cards, CopiesRule::Wrapping).unwrap();
+        assert_eq!(cascade.copies, [2, 3]);
+        assert_eq!(cascade.sources, [vec![(0, 1)], vec![(0, 1), (0, 1)]]);
+    }
+
+    #[test]
+    fn sample_is_valid() {
+        assert_eq!(validate(&parse(SAMPLE)), []);
+    }
+
+    #[test]
+    fn missing_card_is_reported_once() {
+        let cards = [1, 2, 4, 5, 6].map(|id| card_with_wins(id, 1));
+        assert_eq!(
+            validate(&cards),
+            [(
+                3,
+                Anomaly::NonSequentialId {
+                    expected: 3,
+                    found: 4
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn first_card_is_card_1() {
+        let cards = [5, 6, 7].map(|id| card_with_wins(id, 1));
+        assert_eq!(
+            validate(&cards),
+            [(
+                1,
+                Anomaly::NonSequentialId {
+                    expected: 1,
+                    found: 5
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn card_after_the_last_id() {
+        let cards = parse("Card 4294967295: 1 | 2\nCard 1: 1 | 2");
+        assert_eq!(
+            validate(&cards),
+            [
+                (
+                    1,
+                    Anomaly::NonSequentialId {
+                        expected: 1,
+                        found: u32::MAX
+                    }
+                ),
+                (2, Anomaly::IdAfterLast { found: 1 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicates_are_reported_once() {
+        let cards = parse("Card 1: 5 5 5 7 | 1 2 2 3\nCard 2: 5 6 7 8 | 1 2 3 4 5");
+        assert_eq!(
+            validate(&cards),
+            [
+                (1, Anomaly::DuplicateWinningNum(5)),
+                (1, Anomaly::DuplicateScratchedNum(2)),
+                (
+                    2,
+                    Anomaly::ListLengths {
+                        expected: (4, 4),
+                        found: (4, 5)
+                    }
+                ),
+            ]
+        );
+    }
 }
@@ -1,4 +1,8 @@
 use rayon::{iter::ParallelIterator, slice::ParallelSlice};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// a half-open interval of values, start..end
+type Interval = std::ops::Range<u64>;
 
 #[cfg(windows)]
 const BLANK_LINE: &str = "\r\n\r\n";
@@ -14,33 +18,343 @@ struct Range {
 }
 
 impl Range {
+    // written so that it can't overflow, however close to u64::MAX the range is
     fn start_contains(&self, value: u64) -> bool {
-        self.start <= value && value < self.start + self.length
+        self.start <= value && value - self.start < self.length
+    }
+
+    fn source(&self) -> Interval {
+        self.start..self.start + self.length
+    }
+
+    /// Returns where `value` maps to. `value` must be in the range.
+    fn map(&self, value: u64) -> u64 {
+        self.end + (value - self.start)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Map {
+    // the categories this map converts from and to, e.g. seed and soil
+    source: String,
+    destination: String,
+    // the ranges in the order they were given, which decides which range wins when they overlap
     ranges: Vec<Range>,
+    // every value split into pieces, sorted by where each piece starts, along with the range
+    // that maps the piece, or None if it maps to itself. The first piece always starts at 0,
+    // and each piece ends where the next one starts, so we can binary search the starts.
+    lookup: Vec<(u64, Option<usize>)>,
 }
 
 impl Map {
+    fn new(source: String, destination: String, mut ranges: Vec<Range>) -> Map {
+        // Interval ends can't go past u64::MAX, so cut short any range whose source or
+        // destination would. That drops the one source value that would map exactly onto
+        // u64::MAX, since no interval can end after it: that value maps to itself instead.
+        for range in &mut ranges {
+            range.length = range
+                .length
+                .min(u64::MAX - range.start)
+                .min(u64::MAX - range.end);
+        }
+
+        // Give each range the parts of its source that no earlier range has claimed
+        let mut claimed: Vec<(Interval, usize)> = Vec::new();
+        for (i, range) in ranges.iter().enumerate() {
+            let mut unclaimed = vec![range.source()];
+            for (piece_claimed, _) in &claimed {
+                unclaimed = unclaimed
+                    .into_iter()
+                    .flat_map(|piece| {
+                        [
+                            piece.start..piece.end.min(piece_claimed.start),
+                            piece.start.max(piece_claimed.end)..piece.end,
+                        ]
+                    })
+                    .filter(|piece| !piece.is_empty())
+                    .collect();
+            }
+            claimed.extend(unclaimed.into_iter().map(|piece| (piece, i)));
+        }
+        claimed.sort_by_key(|(piece, _)| piece.start);
+
+        // fill in the gaps between the claimed pieces
+        let mut lookup = Vec::new();
+        let mut next = 0;
+        for (piece, i) in claimed {
+            if next < piece.start {
+                lookup.push((next, None));
+            }
+            lookup.push((piece.start, Some(i)));
+            next = piece.end;
+        }
+        // No range reaches u64::MAX, so the last piece always maps to itself,
+        // even if it's only u64::MAX.
+        lookup.push((next, None));
+
+        Map {
+            source,
+            destination,
+            ranges,
+            lookup,
+        }
+    }
+
+    /// Returns the index of the range that maps `value`, or None if it maps to itself.
+    fn find_range(&self, value: u64) -> Option<usize> {
+        // The last piece that starts at or before `value` is the one that contains it.
+        // This binary search always takes the same number of steps and has no early exit,
+        // so the comparison compiles to a conditional move instead of a hard to predict branch,
+        // which makes it quite a bit faster than `partition_point` on maps this small.
+        let mut base = 0;
+        let mut size = self.lookup.len();
+        while size > 1 {
+            let half = size / 2;
+            if self.lookup[base + half].0 <= value {
+                base += half;
+            }
+            size -= half;
+        }
+        self.lookup[base].1
+    }
+
+    /// Same as `find_range`, but checks every range in order. Kept to benchmark against.
+    fn find_range_linear(&self, value: u64) -> Option<usize> {
+        self.ranges
+            .iter()
+            .position(|range| range.start_contains(value))
+    }
+
     fn map(&self, value: u64) -> u64 {
-        for range in &self.ranges {
-            if range.start_contains(value) {
-                return range.end + value - range.start;
+        match self.find_range(value) {
+            Some(i) => self.ranges[i].map(value),
+            None => value,
+        }
+    }
+
+    fn map_linear(&self, value: u64) -> u64 {
+        match self.find_range_linear(value) {
+            Some(i) => self.ranges[i].map(value),
+            None => value,
+        }
+    }
+
+    /// Splits an interval wherever it crosses the edge of a range. Returns each piece, lowest
+    /// first, along with where its start is mapped to. Pieces that fall in no range map to themselves.
+    fn split(&self, interval: Interval) -> Vec<(Interval, u64)> {
+        let mut split = Vec::new();
+
+        // start from the piece that contains the start of the interval
+        let first = self
+            .lookup
+            .partition_point(|&(start, _)| start <= interval.start)
+            - 1;
+
+        for (j, &(start, range)) in self.lookup.iter().enumerate().skip(first) {
+            if start >= interval.end {
+                break;
+            }
+
+            let end = self.lookup.get(j + 1).map_or(u64::MAX, |&(next, _)| next);
+            let piece = start.max(interval.start)..end.min(interval.end);
+            // only an empty interval gives an empty piece, and it has nothing to map
+            if piece.is_empty() {
+                continue;
             }
+            let destination = match range {
+                Some(i) => self.ranges[i].map(piece.start),
+                None => piece.start,
+            };
+            split.push((piece, destination));
         }
-        value
+        split
+    }
+
+    /// Maps a whole interval at once. The interval is split wherever it crosses
+    /// the edge of a range, and each piece is moved by the range it falls in.
+    /// Pieces that fall in no range are kept as they are.
+    fn map_interval(&self, interval: Interval) -> Vec<Interval> {
+        self.split(interval)
+            .into_iter()
+            .map(|(piece, destination)| destination..destination + (piece.end - piece.start))
+            .collect()
+    }
+
+    /// Returns a single map that does the same as this map followed by `next`.
+    fn compose(&self, next: &Map) -> Map {
+        let mut ranges = Vec::new();
+
+        for (piece, destination) in self.split(0..u64::MAX) {
+            let image = destination..destination + (piece.end - piece.start);
+
+            for (image_piece, next_destination) in next.split(image) {
+                let start = piece.start + (image_piece.start - destination);
+                // pieces that end up where they started don't need a range
+                if start != next_destination {
+                    ranges.push(Range {
+                        start,
+                        end: next_destination,
+                        length: image_piece.end - image_piece.start,
+                    });
+                }
+            }
+        }
+        // the pieces don't overlap, so the order of the ranges no longer matters
+        Map::new(self.source.clone(), next.destination.clone(), ranges)
+    }
+
+    /// Returns a map from each destination back to the sources that map to it.
+    fn invert(&self) -> InverseMap {
+        let ranges = self
+            .split(0..u64::MAX)
+            .into_iter()
+            .map(|(piece, destination)| Range {
+                start: destination,
+                end: piece.start,
+                length: piece.end - piece.start,
+            })
+            .collect();
+        InverseMap { ranges }
+    }
+}
+
+/// Folds a chain of maps into a single map that does the same as applying them in order.
+fn compose_all(maps: &[&Map]) -> Map {
+    let (first, rest) = maps
+        .split_first()
+        .expect("Should have at least one map to compose");
+    rest.iter()
+        .fold((*first).clone(), |composed, map| composed.compose(map))
+}
+
+// The inverse of a map. Several sources can map to the same destination, or none at all,
+// so every range that contains a value applies and there is no identity fallback.
+#[derive(Debug)]
+struct InverseMap {
+    ranges: Vec<Range>,
+}
+
+impl InverseMap {
+    /// Returns every value that maps to `value`, lowest first.
+    fn preimages(&self, value: u64) -> Vec<u64> {
+        let mut preimages = self
+            .ranges
+            .iter()
+            .filter(|range| range.start_contains(value))
+            .map(|range| range.map(value))
+            .collect::<Vec<_>>();
+        preimages.sort_unstable();
+        preimages
+    }
+}
+
+// what's odd about the ranges in a map
+#[derive(Debug, Default)]
+struct MapDiagnostics {
+    // pairs of ranges whose sources overlap, and where. The first range wins.
+    overlaps: Vec<(usize, usize, Interval)>,
+    // values below the end of the last range that no range maps, so they map to themselves
+    gaps: Vec<Interval>,
+    // ranges that map their values to themselves
+    identity: Vec<usize>,
+}
+
+impl MapDiagnostics {
+    /// Returns true if the order of the ranges changes what the map does.
+    fn is_ambiguous(&self) -> bool {
+        !self.overlaps.is_empty()
+    }
+}
+
+impl Map {
+    fn diagnose(&self) -> MapDiagnostics {
+        let mut diagnostics = MapDiagnostics::default();
+        let sources = self.ranges.iter().map(Range::source).collect::<Vec<_>>();
+
+        for (i, a) in sources.iter().enumerate() {
+            for (j, b) in sources.iter().enumerate().skip(i + 1) {
+                let overlap = a.start.max(b.start)..a.end.min(b.end);
+                if !overlap.is_empty() {
+                    diagnostics.overlaps.push((i, j, overlap));
+                }
+            }
+        }
+
+        // walk the sources from lowest to highest, looking for space between them
+        let mut sorted = sources.clone();
+        sorted.sort_by_key(|source| source.start);
+        let mut covered_to = 0;
+        for source in sorted.iter().filter(|source| !source.is_empty()) {
+            if covered_to < source.start {
+                diagnostics.gaps.push(covered_to..source.start);
+            }
+            covered_to = covered_to.max(source.end);
+        }
+
+        diagnostics.identity = self
+            .ranges
+            .iter()
+            .enumerate()
+            .filter(|(_, range)| range.start == range.end && range.length > 0)
+            .map(|(i, _)| i)
+            .collect();
+
+        diagnostics
     }
 }
 
 #[derive(Debug)]
 struct AlmanacParseResult {
+    // maps in the order they appear in the file, which needn't be the order they're applied in
     maps: Vec<Map>,
     seeds: Vec<u64>,
 }
 
+impl AlmanacParseResult {
+    /// Returns the chain of maps that converts `from` into `to`, if there is one.
+    fn path(&self, from: &str, to: &str) -> Option<Vec<&Map>> {
+        // breadth first search over the categories, remembering the map that got us to each one
+        let mut came_by = HashMap::<&str, &Map>::new();
+        let mut seen = HashSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(category) = queue.pop_front() {
+            if category == to {
+                // walk back to the start to find the maps we took
+                let mut path = Vec::new();
+                let mut category = to;
+                while category != from {
+                    let map = came_by[category];
+                    path.push(map);
+                    category = &map.source;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for map in self.maps.iter().filter(|map| map.source == category) {
+                if seen.insert(&map.destination) {
+                    came_by.insert(&map.destination, map);
+                    queue.push_back(&map.destination);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the chain of maps that converts seeds into locations.
+    fn seed_to_location(&self) -> Vec<&Map> {
+        self.path("seed", "location")
+            .expect("Should have a path from seed to location")
+    }
+
+    /// Converts a value in category `from` to category `to`, e.g. soil to humidity.
+    fn convert(&self, from: &str, to: &str, value: u64) -> Option<u64> {
+        let path = self.path(from, to)?;
+        Some(path.iter().fold(value, |value, map| map.map(value)))
+    }
+}
+
 fn parse_almanac(input: &str) -> AlmanacParseResult {
     let mut maps = Vec::new();
 
@@ -55,10 +369,18 @@ fn parse_almanac(input: &str) -> AlmanacParseResult {
         .collect();
 
     for map_section in map_sections.filter(|s| !s.is_empty()) {
-        let lines = map_section.lines();
+        let mut lines = map_section.lines();
         let mut ranges = Vec::new();
 
-        for line in lines.skip(1) {
+        // the header looks like "seed-to-soil map:"
+        let (source, destination) = lines
+            .next()
+            .unwrap()
+            .trim_end_matches(" map:")
+            .split_once("-to-")
+            .expect("Should have found a map header");
+
+        for line in lines {
             let mut parts = line.split_whitespace();
 
             let end = parts.next().unwrap().parse().unwrap();
@@ -68,7 +390,11 @@ fn parse_almanac(input: &str) -> AlmanacParseResult {
             ranges.push(Range { start, end, length });
         }
 
-        maps.push(Map { ranges });
+        maps.push(Map::new(
+            source.to_string(),
+            destination.to_string(),
+            ranges,
+        ));
     }
 
     AlmanacParseResult { maps, seeds }
@@ -79,9 +405,12 @@ fn part_1() {
 
     let almanac = parse_almanac(input);
 
+    let maps = almanac.seed_to_location();
+
     let mut min = u64::MAX;
-    for mut seed in almanac.seeds {
-        for map in &almanac.maps {
+    for &seed in &almanac.seeds {
+        let mut seed = seed;
+        for map in &maps {
             seed = map.map(seed);
         }
         min = min.min(seed);
@@ -90,18 +419,42 @@ fn part_1() {
     println!("Part 1 Min: {}", min);
 }
 
-fn part_2() {
-    let input = include_str!("my_input.txt");
+/// Returns the lowest location for any seed in the seed ranges, mapping whole intervals at a time.
+/// Like the brute force, that's u64::MAX if every seed range is empty.
+fn min_location(almanac: &AlmanacParseResult) -> u64 {
+    let mut intervals = almanac
+        .seeds
+        .chunks_exact(2)
+        .map(|seed| seed[0]..seed[0] + seed[1])
+        .filter(|interval| !interval.is_empty())
+        .collect::<Vec<_>>();
 
-    let almanac = parse_almanac(input);
+    for map in almanac.seed_to_location() {
+        intervals = intervals
+            .into_iter()
+            .flat_map(|interval| map.map_interval(interval))
+            .collect();
+    }
+
+    intervals
+        .iter()
+        .map(|interval| interval.start)
+        .min()
+        .unwrap_or(u64::MAX)
+}
 
-    let min = almanac
+/// Returns the lowest location for any seed in the seed ranges, mapping every seed one at a time.
+/// This is slow, but it's a good cross-check for `min_location`.
+fn min_location_brute_force(almanac: &AlmanacParseResult) -> u64 {
+    let maps = almanac.seed_to_location();
+
+    almanac
         .seeds
         .par_chunks_exact(2)
         .map(|seed| {
             let mut min = u64::MAX;
-            for mut seed in seed[0]..=(seed[0] + seed[1]) {
-                for map in &almanac.maps {
+            for mut seed in seed[0]..seed[0] + seed[1] {
+                for map in &maps {
                     seed = map.map(seed);
                 }
 
@@ -110,12 +463,743 @@ fn part_2() {
             min
         })
         .min()
-        .unwrap();
+        .unwrap()
+}
+
+fn part_2(brute_force: bool) {
+    let input = include_str!("my_input.txt");
+
+    let almanac = parse_almanac(input);
 
+    let min = min_location(&almanac);
     println!("Part 2 Min: {}", min);
+
+    if brute_force {
+        let brute_force_min = min_location_brute_force(&almanac);
+        println!("Part 2 Min (brute force): {}", brute_force_min);
+        assert_eq!(min, brute_force_min);
+    }
+}
+
+fn convert(from: &str, to: &str, value: u64) {
+    let input = include_str!("my_input.txt");
+
+    let almanac = parse_almanac(input);
+    match almanac.convert(from, to, value) {
+        Some(converted) => println!("{} {} is {} {}", from, value, to, converted),
+        None => println!("Can't convert from {} to {}", from, to),
+    }
+}
+
+fn seeds_at(location: u64) {
+    let input = include_str!("my_input.txt");
+
+    let almanac = parse_almanac(input);
+    let seeds = compose_all(&almanac.seed_to_location())
+        .invert()
+        .preimages(location);
+
+    println!("Seeds at location {}: {:?}", location, seeds);
 }
 
+// one step of a trace: a map moving some values, and where they go after that
+#[derive(Debug)]
+struct TraceStep<'a> {
+    map: &'a Map,
+    from: Interval,
+    to: Interval,
+    // the range that moved the values, or None if they map to themselves
+    range: Option<usize>,
+    // what the rest of the maps do with `to`
+    next: Vec<TraceStep<'a>>,
+}
+
+/// Traces an interval through a chain of maps. Each map can split the interval
+/// into pieces, so the trace is a tree with a branch for each piece.
+fn trace<'a>(maps: &[&'a Map], interval: Interval) -> Vec<TraceStep<'a>> {
+    let Some((map, rest)) = maps.split_first() else {
+        return Vec::new();
+    };
+
+    map.split(interval)
+        .into_iter()
+        .map(|(from, destination)| {
+            let to = destination..destination + (from.end - from.start);
+            // every value in a piece is mapped by the same range, so checking the first is enough
+            let range = map.find_range(from.start);
+            let next = trace(rest, to.clone());
+            TraceStep {
+                map,
+                from,
+                to,
+                range,
+                next,
+            }
+        })
+        .collect()
+}
+
+fn print_trace(steps: &[TraceStep], depth: usize) {
+    fn format_interval(interval: &Interval) -> String {
+        if interval.end - interval.start == 1 {
+            interval.start.to_string()
+        } else {
+            format!("{}..{}", interval.start, interval.end)
+        }
+    }
+
+    for step in steps {
+        let matched = match step.range {
+            Some(i) => {
+                let range = &step.map.ranges[i];
+                format!(
+                    "range {}: {} {} {}",
+                    i, range.end, range.start, range.length
+                )
+            }
+            None => "identity".to_string(),
+        };
+        println!(
+            "{:indent$}{} {} -> {} {} ({})",
+            "",
+            step.map.source,
+            format_interval(&step.from),
+            step.map.destination,
+            format_interval(&step.to),
+            matched,
+            indent = depth * 2
+        );
+        print_trace(&step.next, depth + 1);
+    }
+}
+
+/// Parses a seed, or an interval of seeds given as `start..end`, which must have seeds in it.
+fn parse_seed_interval(seeds: &str) -> Result<Interval, String> {
+    let parse = |seed: &str| {
+        seed.parse::<u64>()
+            .map_err(|err| format!("bad seed {:?}: {}", seed, err))
+    };
+
+    let interval = match seeds.split_once("..") {
+        Some((start, end)) => parse(start)?..parse(end)?,
+        None => {
+            // intervals are half-open, so they can't hold the very last value
+            let seed = parse(seeds)?;
+            let end = seed
+                .checked_add(1)
+                .ok_or_else(|| format!("can't trace seed {}, it's too big", seed))?;
+            seed..end
+        }
+    };
+
+    if interval.is_empty() {
+        return Err(format!(
+            "no seeds in {}..{}, the start must be before the end",
+            interval.start, interval.end
+        ));
+    }
+    Ok(interval)
+}
+
+/// Prints every intermediate value for a seed, or for an interval of seeds given as `start..end`.
+fn print_seed_trace(seeds: &str) {
+    let input = include_str!("my_input.txt");
+
+    let interval = parse_seed_interval(seeds).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+
+    let almanac = parse_almanac(input);
+    print_trace(&trace(&almanac.seed_to_location(), interval), 0);
+}
+
+fn print_diagnostics() {
+    let input = include_str!("my_input.txt");
+
+    let almanac = parse_almanac(input);
+    for map in &almanac.maps {
+        let diagnostics = map.diagnose();
+        println!("{}-to-{} map:", map.source, map.destination);
+
+        for (i, j, overlap) in &diagnostics.overlaps {
+            println!(
+                "  overlap: ranges {} and {} both map {:?}, range {} wins",
+                i, j, overlap, i
+            );
+        }
+        for gap in &diagnostics.gaps {
+            println!("  gap: {:?} maps to itself", gap);
+        }
+        for &i in &diagnostics.identity {
+            let range = &map.ranges[i];
+            println!(
+                "  identity: range {} maps {:?} to itself",
+                i,
+                range.source()
+            );
+        }
+
+        let end = map
+            .ranges
+            .iter()
+            .map(|range| range.source().end)
+            .max()
+            .unwrap_or(0);
+        println!("  everything from {} up maps to itself", end);
+    }
+}
+
+/// Refuses to go any further if any map has overlapping ranges.
+fn check_ambiguity() {
+    let input = include_str!("my_input.txt");
+
+    let almanac = parse_almanac(input);
+    let ambiguous = almanac
+        .maps
+        .iter()
+        .filter(|map| map.diagnose().is_ambiguous())
+        .map(|map| format!("{}-to-{}", map.source, map.destination))
+        .collect::<Vec<_>>();
+
+    if !ambiguous.is_empty() {
+        eprintln!(
+            "Refusing to use an almanac with overlapping ranges in: {}",
+            ambiguous.join(", ")
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Times mapping seeds from the seed ranges through every map, with and without binary search.
+fn bench() {
+    use std::time::Instant;
+
+    const SEEDS_PER_RANGE: u64 = 1_000_000;
+
+    let input = include_str!("my_input.txt");
+    let almanac = parse_almanac(input);
+    let maps = almanac.seed_to_location();
+
+    let seeds = || {
+        almanac
+            .seeds
+            .chunks_exact(2)
+            .flat_map(|seed| seed[0]..seed[0] + seed[1].min(SEEDS_PER_RANGE))
+    };
+
+    let start = Instant::now();
+    let linear = seeds()
+        .map(|seed| maps.iter().fold(seed, |value, map| map.map_linear(value)))
+        .min();
+    let linear_time = start.elapsed();
+
+    let start = Instant::now();
+    let binary = seeds()
+        .map(|seed| maps.iter().fold(seed, |value, map| map.map(value)))
+        .min();
+    let binary_time = start.elapsed();
+
+    assert_eq!(linear, binary);
+    let count = seeds().count();
+    println!("Mapped {} seeds", count);
+    println!("Linear scan:   {:?}", linear_time);
+    println!("Binary search: {:?}", binary_time);
+}
+
+// SplitMix64, small and good enough for generating test almanacs
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in `range`, which must not be empty.
+    fn in_range(&mut self, range: Interval) -> u64 {
+        range.start + self.next() % (range.end - range.start)
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.in_range(0..i as u64 + 1) as usize);
+        }
+    }
+}
+
+// settings for random almanacs
+#[derive(Debug, Clone)]
+struct AlmanacGenerator {
+    // how many categories, counting seed and location, so there is one less map than this
+    categories: usize,
+    // the most ranges in a single map
+    max_ranges: usize,
+    // how many seed start and length pairs
+    seed_spans: usize,
+    // all sources, destinations and seeds are below this
+    max_value: u64,
+}
+
+impl Default for AlmanacGenerator {
+    fn default() -> Self {
+        AlmanacGenerator {
+            categories: 8,
+            max_ranges: 5,
+            seed_spans: 4,
+            max_value: 200,
+        }
+    }
+}
+
+impl AlmanacGenerator {
+    /// Generates a random almanac in the puzzle's format. The same seed always gives the same almanac.
+    /// Like the puzzle's almanacs, no two ranges in a map have overlapping sources.
+    fn generate(&self, seed: u64) -> String {
+        let mut rng = Rng(seed);
+        let mut sections = Vec::new();
+
+        let seeds = (0..self.seed_spans)
+            .map(|_| {
+                let start = rng.in_range(0..self.max_value);
+                let length = rng.in_range(1..self.max_value - start + 1);
+                format!("{} {}", start, length)
+            })
+            .collect::<Vec<_>>();
+        sections.push(format!("seeds: {}", seeds.join(" ")));
+
+        let categories = (0..self.categories)
+            .map(|i| match i {
+                0 => "seed".to_string(),
+                _ if i == self.categories - 1 => "location".to_string(),
+                _ => format!("category{}", i),
+            })
+            .collect::<Vec<_>>();
+
+        for pair in categories.windows(2) {
+            let mut lines = vec![format!("{}-to-{} map:", pair[0], pair[1])];
+
+            // pick sorted edges, then use every other gap between them as a range's source
+            let num_ranges = rng.in_range(1..self.max_ranges as u64 + 1) as usize;
+            let mut edges = (0..num_ranges * 2)
+                .map(|_| rng.in_range(0..self.max_value))
+                .collect::<Vec<_>>();
+            edges.sort_unstable();
+            edges.dedup();
+
+            let mut ranges = edges
+                .chunks_exact(2)
+                .map(|edge| {
+                    let length = edge[1] - edge[0];
+                    let destination = rng.in_range(0..self.max_value - length + 1);
+                    (destination, edge[0], length)
+                })
+                .collect::<Vec<_>>();
+            // the puzzle doesn't list ranges in order of their sources
+            rng.shuffle(&mut ranges);
+
+            lines.extend(ranges.iter().map(|(destination, source, length)| {
+                format!("{} {} {}", destination, source, length)
+            }));
+            sections.push(lines.join("\n"));
+        }
+
+        // the maps can come in any order too
+        rng.shuffle(&mut sections[1..]);
+
+        sections.join(BLANK_LINE) + "\n"
+    }
+}
+
+// usage: day05 [--brute-force] [--strict] [--diagnostics] [--bench]
+//              [--seeds-at LOCATION] [--convert FROM TO VALUE] [--trace SEED|START..END]
+//              [--generate SEED]
 fn main() {
+    let mut brute_force = false;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--brute-force" => brute_force = true,
+            "--bench" => return bench(),
+            "--diagnostics" => return print_diagnostics(),
+            "--strict" => check_ambiguity(),
+            "--seeds-at" => {
+                let location = args
+                    .next()
+                    .expect("Should have a location after --seeds-at")
+                    .parse()
+                    .unwrap();
+                return seeds_at(location);
+            }
+            "--trace" => {
+                let seeds = args
+                    .next()
+                    .expect("Should have a seed or seeds after --trace");
+                return print_seed_trace(&seeds);
+            }
+            "--generate" => {
+                let seed = args
+                    .next()
+                    .expect("Should have a seed after --generate")
+                    .parse()
+                    .unwrap();
+                print!("{}", AlmanacGenerator::default().generate(seed));
+                return;
+            }
+            "--convert" => {
+                let from = args.next().expect("Should have a category to convert from");
+                let to = args.next().expect("Should have a category to convert to");
+                let value = args
+                    .next()
+                    .expect("Should have a value to convert")
+                    .parse()
+                    .unwrap();
+                return convert(&from, &to, value);
+            }
+            _ => panic!("unknown argument: {}", arg),
+        }
+    }
+
     part_1();
-    part_2();
+    part_2(brute_force);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("input.txt");
+
+    /// A map whose first two ranges overlap on 15..20, where the first one wins.
+    /// The first range maps to itself, and there's a gap between the second and third.
+    fn overlapping_map() -> Map {
+        let range = |start, end, length| Range { start, end, length };
+        Map::new(
+            "a".to_string(),
+            "b".to_string(),
+            vec![range(10, 10, 10), range(15, 0, 10), range(30, 40, 5)],
+        )
+    }
+
+    #[test]
+    fn sample_min_location() {
+        let almanac = parse_almanac(SAMPLE);
+        assert_eq!(min_location(&almanac), 46);
+        assert_eq!(min_location_brute_force(&almanac), 46);
+    }
+
+    #[test]
+    fn empty_seed_ranges_are_ignored() {
+        // the empty range starts partway through a range that maps 5..15 down to 0..10
+        let almanac = parse_almanac("seeds: 7 0 100 10\n\nseed-to-location map:\n0 5 10\n");
+        assert_eq!(almanac.maps[0].split(7..7), vec![]);
+        assert_eq!(min_location(&almanac), 100);
+        assert_eq!(min_location_brute_force(&almanac), 100);
+
+        let almanac = parse_almanac("seeds: 7 0\n\nseed-to-location map:\n0 5 10\n");
+        assert_eq!(min_location(&almanac), u64::MAX);
+        assert_eq!(min_location_brute_force(&almanac), u64::MAX);
+    }
+
+    #[test]
+    fn seed_ranges_end_before_start_plus_length() {
+        // seeds 10 and 11, but not 12, which would map to 0
+        let almanac = parse_almanac("seeds: 10 2\n\nseed-to-location map:\n0 12 1\n");
+        assert_eq!(min_location(&almanac), 10);
+        assert_eq!(min_location_brute_force(&almanac), 10);
+    }
+
+    #[test]
+    fn composed_map_matches_the_chain() {
+        let almanac = parse_almanac(SAMPLE);
+        let composed = compose_all(&almanac.seed_to_location());
+        for seed in 0..200 {
+            let location = almanac.maps.iter().fold(seed, |value, map| map.map(value));
+            assert_eq!(composed.map(seed), location);
+        }
+
+        // composing doesn't care how the chain is grouped
+        let maps = almanac.seed_to_location();
+        let halves = compose_all(&maps[..3]).compose(&compose_all(&maps[3..]));
+        for seed in 0..200 {
+            assert_eq!(halves.map(seed), composed.map(seed));
+        }
+    }
+
+    #[test]
+    fn inverse_finds_every_seed_at_a_location() {
+        let almanac = parse_almanac(SAMPLE);
+        let composed = compose_all(&almanac.seed_to_location());
+        let inverse = composed.invert();
+        for seed in 0..200 {
+            let location = composed.map(seed);
+            let preimages = inverse.preimages(location);
+            assert!(preimages.contains(&seed));
+            assert!(preimages.iter().all(|&s| composed.map(s) == location));
+        }
+    }
+
+    #[test]
+    fn convert_between_categories() {
+        let almanac = parse_almanac(SAMPLE);
+        for seed in 0..200 {
+            let location = almanac.maps.iter().fold(seed, |value, map| map.map(value));
+            assert_eq!(almanac.convert("seed", "location", seed), Some(location));
+        }
+
+        // partway along the chain, and backwards along it where there is no path
+        let soil_to_humidity = |soil| {
+            almanac.maps[1..6]
+                .iter()
+                .fold(soil, |value, map| map.map(value))
+        };
+        for soil in 0..200 {
+            assert_eq!(
+                almanac.convert("soil", "humidity", soil),
+                Some(soil_to_humidity(soil))
+            );
+        }
+        assert_eq!(almanac.convert("location", "seed", 0), None);
+        assert_eq!(almanac.convert("seed", "seed", 5), Some(5));
+    }
+
+    #[test]
+    fn sections_in_any_order() {
+        let mut sections = SAMPLE.split(BLANK_LINE).map(str::trim).collect::<Vec<_>>();
+        sections[1..].reverse();
+        let shuffled = parse_almanac(&sections.join(BLANK_LINE));
+        assert_eq!(min_location(&shuffled), 46);
+        assert_eq!(shuffled.convert("seed", "location", 79), Some(82));
+    }
+
+    #[test]
+    fn sample_diagnostics() {
+        // the sample has no overlaps, and gaps at the bottom of most maps
+        let almanac = parse_almanac(SAMPLE);
+        let diagnostics = almanac.maps[0].diagnose();
+        assert!(!diagnostics.is_ambiguous());
+        assert_eq!(diagnostics.gaps, vec![0..50]);
+        assert!(almanac
+            .maps
+            .iter()
+            .all(|map| !map.diagnose().is_ambiguous()));
+    }
+
+    #[test]
+    fn overlapping_diagnostics() {
+        let diagnostics = overlapping_map().diagnose();
+        assert!(diagnostics.is_ambiguous());
+        assert_eq!(diagnostics.overlaps, vec![(0, 1, 15..20)]);
+        assert_eq!(diagnostics.gaps, vec![0..10, 25..30]);
+        assert_eq!(diagnostics.identity, vec![0]);
+    }
+
+    #[test]
+    fn trace_one_seed() {
+        // seed 79 goes to soil 81, fertilizer 81, water 81, light 74, temperature 78,
+        // humidity 78, location 82, and nothing splits it
+        let almanac = parse_almanac(SAMPLE);
+        let mut steps = trace(&almanac.seed_to_location(), 79..80);
+        let mut values = Vec::new();
+        while let [step] = &mut steps[..] {
+            values.push(step.to.start);
+            steps = std::mem::take(&mut step.next);
+        }
+        assert!(steps.is_empty());
+        assert_eq!(values, vec![81, 81, 81, 74, 78, 78, 82]);
+    }
+
+    #[test]
+    fn trace_seed_interval() {
+        // a seed interval splits into pieces that end up where its seeds do
+        fn leaves(steps: &[TraceStep], found: &mut Vec<u64>) {
+            for step in steps {
+                if step.next.is_empty() {
+                    found.extend(step.to.clone());
+                }
+                leaves(&step.next, found);
+            }
+        }
+
+        let almanac = parse_almanac(SAMPLE);
+        let composed = compose_all(&almanac.seed_to_location());
+        let mut traced = Vec::new();
+        leaves(&trace(&almanac.seed_to_location(), 79..93), &mut traced);
+        traced.sort_unstable();
+        let mut locations = (79..93).map(|seed| composed.map(seed)).collect::<Vec<_>>();
+        locations.sort_unstable();
+        assert_eq!(traced, locations);
+    }
+
+    #[test]
+    fn seed_intervals_to_trace() {
+        assert_eq!(parse_seed_interval("79"), Ok(79..80));
+        assert_eq!(parse_seed_interval("79..93"), Ok(79..93));
+        assert_eq!(
+            parse_seed_interval(&format!("{}", u64::MAX - 1)),
+            Ok(u64::MAX - 1..u64::MAX)
+        );
+
+        assert!(parse_seed_interval("100..50").is_err());
+        assert!(parse_seed_interval("50..50").is_err());
+        assert!(parse_seed_interval(&format!("{}", u64::MAX)).is_err());
+        assert!(parse_seed_interval("seed").is_err());
+        assert!(parse_seed_interval("1..").is_err());
+    }
+
+    #[test]
+    fn binary_search_matches_linear_scan() {
+        let almanac = parse_almanac(SAMPLE);
+        for map in &almanac.maps {
+            for value in 0..200 {
+                assert_eq!(map.find_range(value), map.find_range_linear(value));
+            }
+        }
+
+        // the first range still wins where they overlap
+        let overlapping = overlapping_map();
+        for value in 0..50 {
+            assert_eq!(overlapping.map(value), overlapping.map_linear(value));
+        }
+        assert_eq!(overlapping.map(17), 17);
+        assert_eq!(overlapping.map(22), 7);
+        assert_eq!(
+            overlapping.map_interval(5..40),
+            vec![5..10, 10..20, 5..10, 25..30, 40..45, 35..40]
+        );
+    }
+
+    #[test]
+    fn ranges_at_the_edge_of_u64() {
+        // ranges right up against u64::MAX don't overflow
+        let edge = Map::new(
+            "a".to_string(),
+            "b".to_string(),
+            vec![Range {
+                start: u64::MAX - 5,
+                end: 0,
+                length: 100,
+            }],
+        );
+        assert_eq!(edge.map(u64::MAX - 1), 4);
+        assert_eq!(edge.map(u64::MAX - 6), u64::MAX - 6);
+        assert_eq!(edge.map(u64::MAX), u64::MAX);
+        assert_eq!(
+            edge.map_interval(u64::MAX - 10..u64::MAX),
+            vec![u64::MAX - 10..u64::MAX - 5, 0..5]
+        );
+
+        // the value that would map onto u64::MAX is dropped from its range
+        let onto_max = Map::new(
+            "a".to_string(),
+            "b".to_string(),
+            vec![Range {
+                start: 0,
+                end: u64::MAX - 5,
+                length: 10,
+            }],
+        );
+        assert_eq!(onto_max.map(4), u64::MAX - 1);
+        assert_eq!(onto_max.map(5), 5);
+        assert_eq!(onto_max.map_linear(5), 5);
+        assert_eq!(onto_max.map(6), 6);
+        assert_eq!(
+            onto_max.map_interval(0..10),
+            vec![u64::MAX - 5..u64::MAX, 5..10]
+        );
+    }
+
+    /// Returns the lowest location of any seed, mapping each seed through each map with a linear scan.
+    fn min_seed_location_naive(almanac: &AlmanacParseResult) -> u64 {
+        let maps = almanac.seed_to_location();
+        almanac
+            .seeds
+            .iter()
+            .map(|&seed| maps.iter().fold(seed, |value, map| map.map_linear(value)))
+            .min()
+            .unwrap()
+    }
+
+    /// Compares the optimised answers with mapping one seed at a time on random almanacs.
+    fn check_random_almanacs(generator: &AlmanacGenerator, instances: u64) {
+        for seed in 0..instances {
+            let input = generator.generate(seed);
+            let almanac = parse_almanac(&input);
+            let context = || format!("almanac {}:\n{}", seed, input);
+
+            let composed = compose_all(&almanac.seed_to_location());
+            let part_1 = almanac
+                .seeds
+                .iter()
+                .map(|&seed| composed.map(seed))
+                .min()
+                .unwrap();
+            assert_eq!(part_1, min_seed_location_naive(&almanac), "{}", context());
+
+            // brute force part 2, this time with a linear scan as well
+            let maps = almanac.seed_to_location();
+            let part_2 = almanac
+                .seeds
+                .chunks_exact(2)
+                .flat_map(|seed| seed[0]..seed[0] + seed[1])
+                .map(|seed| maps.iter().fold(seed, |value, map| map.map_linear(value)))
+                .min()
+                .unwrap();
+            assert_eq!(min_location(&almanac), part_2, "{}", context());
+            assert_eq!(min_location_brute_force(&almanac), part_2, "{}", context());
+
+            let inverse = composed.invert();
+            for value in 0..generator.max_value * 2 {
+                let location = maps.iter().fold(value, |value, map| map.map_linear(value));
+                assert_eq!(composed.map(value), location, "{}", context());
+                assert!(
+                    inverse.preimages(location).contains(&value),
+                    "{}",
+                    context()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn random_almanacs_match_brute_force() {
+        check_random_almanacs(&AlmanacGenerator::default(), 200);
+    }
+
+    #[test]
+    fn random_single_map_almanacs_match_brute_force() {
+        let generator = AlmanacGenerator {
+            categories: 2,
+            max_ranges: 12,
+            seed_spans: 1,
+            max_value: 60,
+        };
+        check_random_almanacs(&generator, 200);
+    }
+
+    #[test]
+    fn random_long_almanacs_match_brute_force() {
+        let generator = AlmanacGenerator {
+            categories: 15,
+            max_ranges: 3,
+            seed_spans: 10,
+            max_value: 1000,
+        };
+        check_random_almanacs(&generator, 20);
+    }
+
+    #[test]
+    fn generated_almanacs_are_repeatable() {
+        let generator = AlmanacGenerator::default();
+        assert_eq!(generator.generate(7), generator.generate(7));
+        assert_ne!(generator.generate(7), generator.generate(8));
+
+        let almanac = parse_almanac(&generator.generate(7));
+        assert_eq!(almanac.maps.len(), generator.categories - 1);
+        assert_eq!(almanac.seeds.len(), generator.seed_spans * 2);
+    }
 }
@@ -4,72 +4,745 @@
 //     distance: u64,
 // }
 
+use num_bigint::{BigInt, BigUint};
+use num_integer::{Integer, Roots};
 use std::iter::zip;
 
+/// Returns how many whole milliseconds we can hold the button for and beat the record.
+/// Holding for `h` goes `h * (race_time - h)`, so we win between the roots of
+/// `h^2 - race_time * h + record_distance = 0`. Everything is done in integers so it's exact,
+/// as long as `race_time^2` fits in `T`.
+fn count_winning_holds<T: Integer + Roots + Clone>(race_time: T, record_distance: T) -> T {
+    let two = T::one() + T::one();
+    let time = race_time;
+    let record = record_distance;
+    let half = time.clone() / two.clone();
+    let distance = |hold: &T| hold.clone() * (time.clone() - hold.clone());
+
+    let time_squared = time.clone() * time.clone();
+    let four_records = two.clone() * two.clone() * record.clone();
+    if time_squared <= four_records {
+        // the best we can do is match the record, or not even that
+        return T::zero();
+    }
+    let discriminant = time_squared - four_records;
+
+    // the lower root is (time - sqrt(discriminant)) / 2, so start at or just below
+    // the first winning hold time, and step up until we beat the record
+    let mut first = (time.clone() - discriminant.sqrt()) / two;
+    while first <= half && distance(&first) <= record {
+        first = first + T::one();
+    }
+    if first > half {
+        return T::zero();
+    }
+
+    // the winning hold times are symmetric around time / 2
+    let last = time - first.clone();
+    last - first + T::one()
+}
+
+/// Same as `count_winning_holds`, for races that fit in a u64. Those square to less
+/// than 2^128, so u128 is big enough and much faster than a BigUint.
 fn ways_to_win(race_time: u64, record_distance: u64) -> u64 {
-    let discriminant = (race_time * race_time - 4 * record_distance) as f64;
-    let x1 = (((race_time as f64 - discriminant.sqrt()) / 2.0) + 1.0).floor() as u64;
-    let x2 = (((race_time as f64 + discriminant.sqrt()) / 2.0) - 1.0).ceil() as u64;
-    x2 - x1 + 1
+    count_winning_holds(race_time as u128, record_distance as u128) as u64
+}
+
+// how the boat moves
+#[derive(Debug, Clone)]
+struct RaceModel {
+    // speed gained for each millisecond the button is held, in mm/ms
+    acceleration: BigUint,
+    // the boat can't go faster than this, if there's a limit
+    max_speed: Option<BigUint>,
+    // holding the button for less than this doesn't charge the boat at all
+    min_hold: BigUint,
+    // milliseconds lost after letting go of the button before the boat starts moving
+    penalty: BigUint,
 }
 
-fn part_1() {
-    fn parse_line(line: &str) -> Vec<u64> {
-        line.split(':')
-            .nth(1)
-            .expect("Should have parsed times or distances")
-            .split_whitespace()
-            .flat_map(|n| n.parse().ok())
-            .collect()
+impl Default for RaceModel {
+    // the puzzle's boats
+    fn default() -> Self {
+        RaceModel {
+            acceleration: BigUint::from(1u32),
+            max_speed: None,
+            min_hold: BigUint::ZERO,
+            penalty: BigUint::ZERO,
+        }
     }
+}
 
-    fn parse_input(input: &str) -> (Vec<u64>, Vec<u64>) {
-        let mut lines = input.lines();
-        let times = parse_line(lines.next().expect("Should have parsed times"));
-        let distances = parse_line(lines.next().expect("Should have parsed distances"));
-        (times, distances)
+impl RaceModel {
+    /// Returns how far the boat goes if we hold the button for `hold` milliseconds.
+    fn distance(&self, race_time: &BigUint, hold: &BigUint) -> BigUint {
+        if hold < &self.min_hold || hold + &self.penalty >= *race_time {
+            return BigUint::ZERO;
+        }
+
+        let mut speed = &self.acceleration * hold;
+        if let Some(max_speed) = &self.max_speed {
+            speed = speed.min(max_speed.clone());
+        }
+        speed * (race_time - hold - &self.penalty)
     }
 
-    let input = include_str!("my_input.txt");
+    /// Returns how many whole milliseconds we can hold the button for and beat the record.
+    fn ways_to_win(&self, race_time: &BigUint, record_distance: &BigUint) -> BigUint {
+        if self.max_speed.is_some() || self.min_hold > BigUint::ZERO {
+            return self.ways_to_win_by_search(race_time, record_distance);
+        }
+        if self.acceleration == BigUint::ZERO || self.penalty >= *race_time {
+            return BigUint::ZERO;
+        }
 
-    let (times, distances) = parse_input(input);
+        // Without a speed limit or minimum hold, the boat goes acceleration * h * (time - h)
+        // where time is what's left after the penalty. That beats the record exactly when
+        // h * (time - h) beats record / acceleration rounded down, which is the puzzle's race.
+        let time = race_time - &self.penalty;
+        let record = record_distance / &self.acceleration;
+        match (u64::try_from(&time), u64::try_from(&record)) {
+            (Ok(time), Ok(record)) => BigUint::from(ways_to_win(time, record)),
+            _ => count_winning_holds(time, record),
+        }
+    }
 
-    let races = zip(times.iter(), distances.iter());
+    /// Returns the hold times worth searching: holding for less than the minimum, or so long
+    /// that the penalty uses up the rest of the race, goes nowhere. None if nothing goes anywhere.
+    fn hold_times(&self, race_time: &BigUint) -> Option<(BigUint, BigUint)> {
+        let lowest = self.min_hold.clone();
+        if *race_time <= &lowest + &self.penalty {
+            return None;
+        }
+        let highest = race_time - &self.penalty - 1u32;
+        Some((lowest, highest))
+    }
+
+    /// Returns the hold time that goes the furthest, the shortest one if there's a tie.
+    /// The distance goes up (or stays level) as we hold longer, up to the best hold time,
+    /// then only goes down, so the best one is the first that goes as far as the next one.
+    fn best_hold(&self, race_time: &BigUint) -> BigUint {
+        let Some((lowest, highest)) = self.hold_times(race_time) else {
+            return BigUint::ZERO;
+        };
+        let distance = |hold: &BigUint| self.distance(race_time, hold);
 
-    //let races = times.iter().zip(distances.iter());
+        first_where(&lowest, &highest, |hold| {
+            distance(&(hold + 1u32)) <= distance(hold)
+        })
+    }
 
-    let product = races.map(|(t, d)| ways_to_win(*t, *d)).product::<u64>();
+    /// Returns the shortest and longest hold times that beat the record, if any do.
+    /// Each side of the best hold time only goes one way, so we can binary search them.
+    fn winning_window(
+        &self,
+        race_time: &BigUint,
+        record_distance: &BigUint,
+    ) -> Option<(BigUint, BigUint)> {
+        let (lowest, highest) = self.hold_times(race_time)?;
+        let distance = |hold: &BigUint| self.distance(race_time, hold);
 
-    println!("Part 1 Product: {}", product);
+        let best = self.best_hold(race_time);
+        if distance(&best) <= *record_distance {
+            return None;
+        }
+
+        let first = first_where(&lowest, &best, |hold| distance(hold) > *record_distance);
+        // the first hold time after the best one that doesn't beat the record
+        let after_last = first_where(&best, &(&highest + 1u32), |hold| {
+            distance(hold) <= *record_distance
+        });
+        Some((first, after_last - 1u32))
+    }
+
+    /// Same as `ways_to_win`, but works for any model by binary searching the hold times.
+    fn ways_to_win_by_search(&self, race_time: &BigUint, record_distance: &BigUint) -> BigUint {
+        match self.winning_window(race_time, record_distance) {
+            Some((first, last)) => last - first + 1u32,
+            None => BigUint::ZERO,
+        }
+    }
+
+    fn report(&self, name: String, race_time: BigUint, record_distance: BigUint) -> RaceReport {
+        let best_hold = self.best_hold(&race_time);
+        let best_distance = self.distance(&race_time, &best_hold);
+        RaceReport {
+            window: self.winning_window(&race_time, &record_distance),
+            margin: BigInt::from(best_distance.clone()) - BigInt::from(record_distance.clone()),
+            ways_to_win: self.ways_to_win(&race_time, &record_distance),
+            name,
+            race_time,
+            record_distance,
+            best_hold,
+            best_distance,
+        }
+    }
 }
 
-fn part_2() {
-    fn parse_line(line: &str) -> u64 {
-        let (_title, nums) = line
-            .split_once(':')
-            .expect("Should have parsed times or distances");
-        nums.replace(' ', "")
-            .parse()
-            .expect("Should have found a single number")
+// everything about how a race can be won
+#[derive(Debug)]
+struct RaceReport {
+    name: String,
+    race_time: BigUint,
+    record_distance: BigUint,
+    // the shortest and longest hold times that beat the record, if any do
+    window: Option<(BigUint, BigUint)>,
+    best_hold: BigUint,
+    best_distance: BigUint,
+    // how far past the record the best hold time goes, negative if it doesn't reach it
+    margin: BigInt,
+    ways_to_win: BigUint,
+}
+
+fn print_report_table(reports: &[RaceReport]) {
+    println!(
+        "{:>8} {:>16} {:>18} {:>16} {:>16} {:>16} {:>18} {:>18} {:>16}",
+        "Race",
+        "Time",
+        "Record",
+        "Min Hold",
+        "Max Hold",
+        "Best Hold",
+        "Best Distance",
+        "Margin",
+        "Ways"
+    );
+    for report in reports {
+        let (min_hold, max_hold) = match &report.window {
+            Some((first, last)) => (first.to_string(), last.to_string()),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        println!(
+            "{:>8} {:>16} {:>18} {:>16} {:>16} {:>16} {:>18} {:>18} {:>16}",
+            report.name,
+            report.race_time,
+            report.record_distance,
+            min_hold,
+            max_hold,
+            report.best_hold,
+            report.best_distance,
+            report.margin,
+            report.ways_to_win
+        );
     }
+}
+
+fn print_report_json(reports: &[RaceReport]) {
+    // the numbers can be too big for some JSON readers, but they're still valid JSON numbers
+    let json = reports
+        .iter()
+        .map(|report| {
+            let (min_hold, max_hold) = match &report.window {
+                Some((first, last)) => (first.to_string(), last.to_string()),
+                None => ("null".to_string(), "null".to_string()),
+            };
+            format!(
+                concat!(
+                    "  {{\"race\": \"{}\", \"time\": {}, \"record\": {}, ",
+                    "\"min_hold\": {}, \"max_hold\": {}, \"best_hold\": {}, ",
+                    "\"best_distance\": {}, \"margin\": {}, \"ways_to_win\": {}}}"
+                ),
+                report.name,
+                report.race_time,
+                report.record_distance,
+                min_hold,
+                max_hold,
+                report.best_hold,
+                report.best_distance,
+                report.margin,
+                report.ways_to_win
+            )
+        })
+        .collect::<Vec<_>>();
+    println!("[\n{}\n]", json.join(",\n"));
+}
+
+/// Returns the first value in `low..=high` where `predicate` is true, or `high` if it never is.
+/// `predicate` must be false up to some value and true from then on.
+fn first_where(low: &BigUint, high: &BigUint, predicate: impl Fn(&BigUint) -> bool) -> BigUint {
+    let mut low = low.clone();
+    let mut high = high.clone();
+    while low < high {
+        let mid = (&low + &high) / 2u32;
+        if predicate(&mid) {
+            high = mid;
+        } else {
+            low = mid + 1u32;
+        }
+    }
+    low
+}
+
+// what's wrong with a race sheet
+#[derive(Debug, PartialEq, Eq)]
+enum SheetError {
+    MissingLine(&'static str),
+    WrongLabel {
+        line: usize,
+        expected: &'static str,
+        found: String,
+    },
+    BadNumber {
+        line: usize,
+        token: String,
+    },
+    TooBig {
+        line: usize,
+        token: String,
+    },
+    MismatchedColumns {
+        times: usize,
+        distances: usize,
+    },
+    NoRaces,
+    ExtraLine(usize),
+}
+
+impl std::fmt::Display for SheetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SheetError::MissingLine(label) => write!(f, "missing the {} line", label),
+            SheetError::WrongLabel {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {}:, found {:?}",
+                line, expected, found
+            ),
+            SheetError::BadNumber { line, token } => {
+                write!(f, "line {}: {:?} is not a number", line, token)
+            }
+            SheetError::TooBig { line, token } => {
+                write!(f, "line {}: {} is too big", line, token)
+            }
+            SheetError::MismatchedColumns { times, distances } => {
+                write!(f, "{} times but {} distances", times, distances)
+            }
+            SheetError::NoRaces => write!(f, "no races"),
+            SheetError::ExtraLine(line) => write!(f, "line {}: unexpected line", line),
+        }
+    }
+}
+
+/// Checks the sheet's labels and numbers, and returns the number tokens on the
+/// Time and Distance lines.
+fn parse_sheet(input: &str) -> Result<(Vec<&str>, Vec<&str>), SheetError> {
+    fn parse_line<'a>(
+        line_number: usize,
+        line: Option<&'a str>,
+        label: &'static str,
+    ) -> Result<Vec<&'a str>, SheetError> {
+        let line = line.ok_or(SheetError::MissingLine(label))?;
+        let (found, nums) = line.split_once(':').unwrap_or((line, ""));
+        if found.trim() != label {
+            return Err(SheetError::WrongLabel {
+                line: line_number,
+                expected: label,
+                found: found.to_string(),
+            });
+        }
+
+        let tokens = nums.split_whitespace().collect::<Vec<_>>();
+        if let Some(token) = tokens
+            .iter()
+            .find(|token| !token.chars().all(|c| c.is_ascii_digit()))
+        {
+            return Err(SheetError::BadNumber {
+                line: line_number,
+                token: token.to_string(),
+            });
+        }
+        Ok(tokens)
+    }
+
+    let mut lines = input.lines();
+    let times = parse_line(1, lines.next(), "Time")?;
+    let distances = parse_line(2, lines.next(), "Distance")?;
+
+    if let Some(i) = lines.position(|line| !line.trim().is_empty()) {
+        return Err(SheetError::ExtraLine(i + 3));
+    }
+    if times.len() != distances.len() {
+        return Err(SheetError::MismatchedColumns {
+            times: times.len(),
+            distances: distances.len(),
+        });
+    }
+    if times.is_empty() {
+        return Err(SheetError::NoRaces);
+    }
+    Ok((times, distances))
+}
 
-    fn parse_input(input: &str) -> (u64, u64) {
-        let mut lines = input.lines();
-        let times = parse_line(lines.next().expect("Should have parsed times"));
-        let distances = parse_line(lines.next().expect("Should have parsed distances"));
-        (times, distances)
+/// Reads each race's time and record distance from the sheet.
+fn parse_races(input: &str) -> Result<Vec<(u64, u64)>, SheetError> {
+    fn parse_number(line: usize, token: &str) -> Result<u64, SheetError> {
+        // the tokens are all digits, so the only way to fail is being too big
+        token.parse().map_err(|_| SheetError::TooBig {
+            line,
+            token: token.to_string(),
+        })
     }
 
+    let (times, distances) = parse_sheet(input)?;
+    zip(times, distances)
+        .map(|(time, distance)| Ok((parse_number(1, time)?, parse_number(2, distance)?)))
+        .collect()
+}
+
+/// Reads the sheet as a single race, ignoring the spaces between the numbers.
+fn parse_kerned_race(input: &str) -> Result<(BigUint, BigUint), SheetError> {
+    let (times, distances) = parse_sheet(input)?;
+    let time = times.concat().parse().unwrap();
+    let distance = distances.concat().parse().unwrap();
+    Ok((time, distance))
+}
+
+/// Stops before a corrupted sheet gets scored.
+fn refuse(err: SheetError) -> ! {
+    eprintln!("Refusing to score the race sheet: {}", err);
+    std::process::exit(1);
+}
+
+fn part_1(model: &RaceModel) {
     let input = include_str!("my_input.txt");
 
-    let (time, distance) = parse_input(input);
+    let races = parse_races(input).unwrap_or_else(|err| refuse(err));
 
-    let ways_to_win = ways_to_win(time, distance);
+    let product = races
+        .iter()
+        .map(|(t, d)| model.ways_to_win(&BigUint::from(*t), &BigUint::from(*d)))
+        .product::<BigUint>();
+
+    println!("Part 1 Product: {}", product);
+}
+
+fn part_2(model: &RaceModel) {
+    let input = include_str!("my_input.txt");
+
+    let (time, distance) = parse_kerned_race(input).unwrap_or_else(|err| refuse(err));
+
+    let ways_to_win = model.ways_to_win(&time, &distance);
 
     println!("Part 2 Ways to Win: {}", ways_to_win);
 }
 
+/// Prints a report for each race, and for the kerned race, as a table or as JSON.
+fn report(model: &RaceModel, format: &str) {
+    let input = include_str!("my_input.txt");
+
+    let mut reports = parse_races(input)
+        .unwrap_or_else(|err| refuse(err))
+        .into_iter()
+        .enumerate()
+        .map(|(i, (time, distance))| {
+            model.report(
+                (i + 1).to_string(),
+                BigUint::from(time),
+                BigUint::from(distance),
+            )
+        })
+        .collect::<Vec<_>>();
+    let (time, distance) = parse_kerned_race(input).unwrap_or_else(|err| refuse(err));
+    reports.push(model.report("kerned".to_string(), time, distance));
+
+    match format {
+        "table" => print_report_table(&reports),
+        "json" => print_report_json(&reports),
+        _ => panic!("unknown report format: {}", format),
+    }
+}
+
+// usage: day06 [--acceleration N] [--max-speed N] [--min-hold N] [--penalty N]
+//              [--report table|json]
 fn main() {
-    part_1();
-    part_2();
+    let mut model = RaceModel::default();
+    let mut report_format = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == "--report" {
+            report_format = Some(args.next().expect("Should have a format after --report"));
+            continue;
+        }
+
+        let mut value = || -> BigUint {
+            args.next()
+                .unwrap_or_else(|| panic!("Should have a number after {}", arg))
+                .parse()
+                .unwrap()
+        };
+
+        match arg.as_str() {
+            "--acceleration" => model.acceleration = value(),
+            "--max-speed" => model.max_speed = Some(value()),
+            "--min-hold" => model.min_hold = value(),
+            "--penalty" => model.penalty = value(),
+            _ => panic!("unknown argument: {}", arg),
+        }
+    }
+
+    if let Some(format) = report_format {
+        return report(&model, &format);
+    }
+
+    part_1(&model);
+    part_2(&model);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts the winning hold times one at a time.
+    fn count(time: u64, record: u64) -> u64 {
+        (0..=time)
+            .filter(|hold| hold * (time - hold) > record)
+            .count() as u64
+    }
+
+    #[test]
+    fn sample_races() {
+        assert_eq!(ways_to_win(7, 9), 4);
+        assert_eq!(ways_to_win(15, 40), 8);
+        assert_eq!(ways_to_win(30, 200), 9);
+        assert_eq!(ways_to_win(71530, 940200), 71503);
+    }
+
+    #[test]
+    fn perfect_squares() {
+        // holding for a root matches the record exactly and doesn't win
+        assert_eq!(ways_to_win(10, 21), 3);
+        assert_eq!(ways_to_win(10, 24), 1);
+        assert_eq!(ways_to_win(10, 25), 0);
+        assert_eq!(ways_to_win(10, 16), 5);
+    }
+
+    #[test]
+    fn races_that_cant_be_won() {
+        // or can't even be moved in
+        assert_eq!(ways_to_win(0, 0), 0);
+        assert_eq!(ways_to_win(1, 0), 0);
+        assert_eq!(ways_to_win(2, 1), 0);
+        assert_eq!(ways_to_win(5, 6), 0);
+        assert_eq!(ways_to_win(5, 100), 0);
+    }
+
+    #[test]
+    fn matches_counting_every_hold_time() {
+        for time in 0..100 {
+            for record in 0..time * time / 4 + 2 {
+                assert_eq!(
+                    ways_to_win(time, record),
+                    count(time, record),
+                    "{} {}",
+                    time,
+                    record
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn huge_races() {
+        // time * time overflows a u64
+        assert_eq!(ways_to_win(u64::MAX, 0), u64::MAX - 1);
+        assert_eq!(ways_to_win(u64::MAX, u64::MAX), u64::MAX - 3);
+
+        // holding for exactly half matches the record, and nothing else comes close
+        let half = 1u64 << 31;
+        assert_eq!(ways_to_win(2 * half, half * half), 0);
+        assert_eq!(ways_to_win(2 * half, half * half - 1), 1);
+        assert_eq!(ways_to_win(2 * half, half * half - 4), 3);
+    }
+
+    #[test]
+    fn big_integers_agree_with_u128() {
+        let big = |n: u64| BigUint::from(n);
+        for time in 0..60 {
+            for record in 0..time * time / 4 + 2 {
+                assert_eq!(
+                    count_winning_holds(big(time), big(record)),
+                    big(count(time, record))
+                );
+            }
+        }
+        for (time, record) in [(u64::MAX, 0), (u64::MAX, u64::MAX), (71530, 940200)] {
+            assert_eq!(
+                count_winning_holds(big(time), big(record)),
+                big(ways_to_win(time, record))
+            );
+        }
+    }
+
+    #[test]
+    fn race_too_long_for_a_u64() {
+        // even the number of ways to win doesn't fit in a u64. With time 10^30 and
+        // record 10^58 we win between the roots 10^28 * (50 +- sqrt(2400)),
+        // which are 1.0102051443364...e28 and 98.9897948556635...e28
+        let time: BigUint = "1000000000000000000000000000000".parse().unwrap();
+        let record: BigUint = "10000000000000000000000000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        let ways = count_winning_holds(time.clone(), record.clone());
+        assert!(ways > BigUint::from(u64::MAX));
+
+        // the first and last winning hold times beat the record, and the ones just outside don't
+        let first = (&time - &ways + 1u32) / 2u32;
+        let last = &first + &ways - 1u32;
+        let distance = |hold: &BigUint| hold * (&time - hold);
+        assert!(distance(&first) > record && distance(&last) > record);
+        assert!(distance(&(&first - 1u32)) <= record && distance(&(&last + 1u32)) <= record);
+    }
+
+    #[test]
+    fn models_match_trying_every_hold_time() {
+        let big = |n: u64| BigUint::from(n);
+        let models = [
+            RaceModel::default(),
+            RaceModel {
+                acceleration: big(3),
+                penalty: big(2),
+                ..RaceModel::default()
+            },
+            RaceModel {
+                max_speed: Some(big(4)),
+                ..RaceModel::default()
+            },
+            RaceModel {
+                acceleration: big(2),
+                max_speed: Some(big(7)),
+                min_hold: big(3),
+                penalty: big(1),
+            },
+            RaceModel {
+                min_hold: big(10),
+                ..RaceModel::default()
+            },
+        ];
+        for model in &models {
+            for time in 0..40 {
+                for record in 0..200 {
+                    let (time, record) = (big(time), big(record));
+                    let count = (0..=time.clone().try_into().unwrap())
+                        .filter(|&hold: &u64| model.distance(&time, &big(hold)) > record)
+                        .count() as u64;
+                    assert_eq!(model.ways_to_win(&time, &record), big(count), "{:?}", model);
+                    assert_eq!(
+                        model.ways_to_win_by_search(&time, &record),
+                        big(count),
+                        "{:?}",
+                        model
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn search_works_for_huge_races() {
+        let time: BigUint = "1000000000000000000000000000000".parse().unwrap();
+        let record: BigUint = "10000000000000000000000000000000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            RaceModel::default().ways_to_win_by_search(&time, &record),
+            count_winning_holds(time, record)
+        );
+    }
+
+    #[test]
+    fn sample_race_report() {
+        // the sample's third race wins holding for 11 to 19, best at 15 going 225
+        let big = |n: u64| BigUint::from(n);
+        let report = RaceModel::default().report("3".to_string(), big(30), big(200));
+        assert_eq!(report.window, Some((big(11), big(19))));
+        assert_eq!(
+            (report.best_hold, report.best_distance),
+            (big(15), big(225))
+        );
+        assert_eq!(report.margin, BigInt::from(25));
+        assert_eq!(report.ways_to_win, big(9));
+    }
+
+    #[test]
+    fn lost_race_report() {
+        let big = |n: u64| BigUint::from(n);
+        let report = RaceModel::default().report("lost".to_string(), big(5), big(6));
+        assert_eq!(report.window, None);
+        assert_eq!(report.margin, BigInt::from(0));
+        assert_eq!(report.ways_to_win, big(0));
+
+        // a penalty as long as the race means the boat never moves
+        let model = RaceModel {
+            penalty: big(5),
+            ..RaceModel::default()
+        };
+        let report = model.report("stuck".to_string(), big(5), big(0));
+        assert_eq!(report.window, None);
+        assert_eq!(report.best_distance, big(0));
+    }
+
+    #[test]
+    fn race_sheets() {
+        // the sample sheet parses, and broken sheets don't
+        let big = |n: u64| BigUint::from(n);
+        let sample = include_str!("input.txt");
+        assert_eq!(parse_races(sample), Ok(vec![(7, 9), (15, 40), (30, 200)]));
+        assert_eq!(parse_kerned_race(sample), Ok((big(71530), big(940200))));
+        let broken = |from: &str, to: &str| parse_races(&sample.replace(from, to));
+        assert_eq!(
+            broken("Time:", "Tim:"),
+            Err(SheetError::WrongLabel {
+                line: 1,
+                expected: "Time",
+                found: "Tim".to_string()
+            })
+        );
+        assert_eq!(
+            broken("Distance:  9  40  200", "Distance:  9  40  2O0"),
+            Err(SheetError::BadNumber {
+                line: 2,
+                token: "2O0".to_string()
+            })
+        );
+        assert_eq!(
+            broken("  200", "  -200"),
+            Err(SheetError::BadNumber {
+                line: 2,
+                token: "-200".to_string()
+            })
+        );
+        assert_eq!(
+            broken("  30", ""),
+            Err(SheetError::MismatchedColumns {
+                times: 2,
+                distances: 3
+            })
+        );
+        assert_eq!(
+            broken("  30", "  99999999999999999999"),
+            Err(SheetError::TooBig {
+                line: 1,
+                token: "99999999999999999999".to_string()
+            })
+        );
+        assert_eq!(
+            parse_races("Time:      7\n"),
+            Err(SheetError::MissingLine("Distance"))
+        );
+        assert_eq!(parse_races("Time:\nDistance:\n"), Err(SheetError::NoRaces));
+        assert_eq!(
+            parse_races("Time: 7\nDistance: 9\nTime: 8\n"),
+            Err(SheetError::ExtraLine(3))
+        );
+        // the kerned race is big enough for anything
+        assert_eq!(
+            parse_kerned_race(&sample.replace("  30", "  99999999999999999999"))
+                .map(|(time, _)| time),
+            Ok("71599999999999999999999".parse().unwrap())
+        );
+    }
 }
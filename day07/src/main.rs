@@ -1,16 +1,27 @@
 use itertools::Itertools;
-use std::{cmp::Ordering, fmt::Debug};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, HashSet},
+    fmt::Debug,
+    iter::zip,
+};
 
+#[derive(Debug, Clone)]
 struct Hand {
     cards: Vec<u8>,
+    // the suit of each card, if the ruleset has suits
+    suits: Vec<char>,
     bid: u32,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
 enum HandType {
     FiveOfAKind,
+    StraightFlush,
     FourOfAKind,
     FullHouse,
+    Flush,
+    Straight,
     ThreeOfAKind,
     TwoPair,
     OnePair,
@@ -26,88 +37,639 @@ impl Hand {
         }
         Ordering::Equal
     }
+}
 
-    fn score_part_1(&self) -> HandType {
-        let mut counts = [0; 13];
-        for &c in &self.cards {
-            counts[13 - c as usize] += 1;
-        }
-
-        if counts.iter().any(|&c| c == 5) {
-            HandType::FiveOfAKind
-        } else if counts.iter().any(|&c| c == 4) {
-            HandType::FourOfAKind
-        } else if counts.iter().any(|&c| c == 3) && counts.iter().any(|&c| c == 2) {
-            HandType::FullHouse
-        } else if counts.iter().any(|&c| c == 3) {
-            HandType::ThreeOfAKind
-        } else if counts.iter().filter(|&&c| c == 2).count() == 2 {
-            HandType::TwoPair
-        } else if counts.iter().any(|&c| c == 2) {
-            HandType::OnePair
-        } else {
-            HandType::HighCard
+// the rules of a game of cards
+#[derive(Debug, Clone)]
+struct Ruleset {
+    // the cards, strongest first
+    cards: &'static str,
+    // cards that count as whatever card makes the best hand
+    wildcards: &'static str,
+    // how many cards in a hand
+    hand_size: usize,
+    // each hand type, strongest first, with the groups of matching cards it needs from biggest
+    // to smallest. A hand is the first type whose groups are all no bigger than the hand's.
+    hand_types: Vec<(HandType, Vec<u8>)>,
+    // the suits, if the cards have them. With suits, a hand all of one suit is a flush.
+    suits: &'static str,
+    // whether a hand of consecutive cards is a straight
+    straights: bool,
+    // how hands of the same type are ordered
+    tiebreak: Tiebreak,
+}
+
+// how to order two hands of the same type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tiebreak {
+    // compare the first cards, then the second cards, and so on, like Camel Cards
+    InOrder,
+    // compare the biggest groups first, then the highest cards, like poker
+    ByGroup,
+}
+
+impl Ruleset {
+    // the hand types of Camel Cards
+    fn camel_hand_types() -> Vec<(HandType, Vec<u8>)> {
+        vec![
+            (HandType::FiveOfAKind, vec![5]),
+            (HandType::FourOfAKind, vec![4]),
+            (HandType::FullHouse, vec![3, 2]),
+            (HandType::ThreeOfAKind, vec![3]),
+            (HandType::TwoPair, vec![2, 2]),
+            (HandType::OnePair, vec![2]),
+            (HandType::HighCard, vec![]),
+        ]
+    }
+
+    fn part_1() -> Ruleset {
+        Ruleset {
+            cards: "AKQJT98765432",
+            wildcards: "",
+            hand_size: 5,
+            hand_types: Ruleset::camel_hand_types(),
+            suits: "",
+            straights: false,
+            tiebreak: Tiebreak::InOrder,
+        }
+    }
+
+    // J is a joker, and the weakest card
+    fn part_2() -> Ruleset {
+        Ruleset {
+            cards: "AKQT98765432J",
+            wildcards: "J",
+            hand_size: 5,
+            hand_types: Ruleset::camel_hand_types(),
+            suits: "",
+            straights: false,
+            tiebreak: Tiebreak::InOrder,
+        }
+    }
+
+    // standard poker, with straights and flushes but no wildcards
+    fn poker() -> Ruleset {
+        Ruleset {
+            cards: "AKQJT98765432",
+            wildcards: "",
+            hand_size: 5,
+            hand_types: vec![
+                (HandType::FourOfAKind, vec![4]),
+                (HandType::FullHouse, vec![3, 2]),
+                (HandType::ThreeOfAKind, vec![3]),
+                (HandType::TwoPair, vec![2, 2]),
+                (HandType::OnePair, vec![2]),
+                (HandType::HighCard, vec![]),
+            ],
+            suits: "SHDC",
+            straights: true,
+            tiebreak: Tiebreak::ByGroup,
+        }
+    }
+
+    /// Returns the strength of a card, higher is stronger.
+    fn strength(&self, card: char) -> u8 {
+        let position = self
+            .cards
+            .find(card)
+            .unwrap_or_else(|| panic!("{} is not a card", card));
+        (self.cards.len() - position) as u8
+    }
+
+    fn is_wildcard(&self, strength: u8) -> bool {
+        self.wildcards
+            .chars()
+            .any(|wildcard| self.strength(wildcard) == strength)
+    }
+
+    fn hand_type(&self, hand: &Hand) -> HandType {
+        // Strengths are u8, so fixed arrays can hold a count for every card without
+        // allocating. This runs for every comparison when sorting, so that adds up.
+        let mut counts = [0u8; 256];
+        for &c in &hand.cards {
+            counts[c as usize] += 1;
+        }
+        // take the wildcards out of the counts, looking up each one's strength just once
+        let mut wildcards = 0;
+        for wildcard in self.wildcards.chars() {
+            wildcards += std::mem::take(&mut counts[self.strength(wildcard) as usize]);
+        }
+
+        let mut groups = [0u8; 256];
+        let mut len = 0;
+        for &count in counts[..=self.cards.len()]
+            .iter()
+            .filter(|&&count| count != 0)
+        {
+            groups[len] = count;
+            len += 1;
+        }
+        groups[..len].sort_unstable_by(|a, b| b.cmp(a));
+
+        // the wildcards do the most good joining the biggest group, or are the only
+        // group if every card is wild
+        groups[0] += wildcards;
+        let groups = &groups[..len.max(1)];
+
+        let hand_type = self
+            .hand_types
+            .iter()
+            .find(|(_, needed)| {
+                needed.len() <= groups.len()
+                    && zip(needed, groups).all(|(needed, group)| group >= needed)
+            })
+            .map(|(hand_type, _)| *hand_type)
+            .expect("Should have a hand type for every hand");
+
+        // a straight or flush can beat the groups, and a hand type that comes first is stronger
+        let straight = self.straights && self.straight_high_card(hand).is_some();
+        let flush = !self.suits.is_empty() && {
+            assert_eq!(
+                hand.suits.len(),
+                hand.cards.len(),
+                "Should have a suit for every card when the rules have suits"
+            );
+            hand.suits.iter().all_equal()
+        };
+        let poker_type = match (straight, flush) {
+            (true, true) => HandType::StraightFlush,
+            (false, true) => HandType::Flush,
+            (true, false) => HandType::Straight,
+            (false, false) => HandType::HighCard,
+        };
+        hand_type.min(poker_type)
+    }
+
+    /// Returns the strength of the highest card if the hand is a straight. In the
+    /// wheel, A2345, the ace is low so the five is the highest card.
+    fn straight_high_card(&self, hand: &Hand) -> Option<u8> {
+        // a straight has no two cards the same
+        let mut seen = [false; 256];
+        for &c in &hand.cards {
+            if std::mem::replace(&mut seen[c as usize], true) {
+                return None;
+            }
         }
+
+        let high = *hand.cards.iter().max()?;
+        let low = *hand.cards.iter().min()?;
+        let len = hand.cards.len() as u8;
+        if high - low + 1 == len {
+            return Some(high);
+        }
+
+        // the wheel: an ace and the lowest cards
+        let ace = self.cards.len() as u8;
+        if seen[ace as usize] && (1..len).all(|c| seen[c as usize]) {
+            return Some(len - 1);
+        }
+        None
     }
 
-    fn score_part_2(&self) -> HandType {
-        let mut counts = [0; 13];
-        for &c in &self.cards {
-            counts[13 - c as usize] += 1;
+    /// Returns the card strengths in the order they break ties between hands of the same type.
+    /// In order, that's the order they were dealt in. By group, it's the biggest group first,
+    /// with higher cards first within groups of the same size, and a wheel ranks as a five
+    /// high straight.
+    fn tiebreak(&self, hand: &Hand) -> Vec<u8> {
+        if self.tiebreak == Tiebreak::InOrder {
+            return hand.cards.clone();
         }
 
-        let jokers = counts[12];
-        let counts = counts[0..12]
+        let straight = self
+            .straights
+            .then(|| self.straight_high_card(hand))
+            .flatten();
+        if let Some(high_card) = straight {
+            return (0..self.hand_size as u8).map(|i| high_card - i).collect();
+        }
+
+        let counts = hand.cards.iter().counts();
+        hand.cards
             .iter()
             .copied()
-            .filter(|x| *x != 0)
-            .sorted()
-            .rev()
+            .sorted_by_key(|c| (Reverse(counts[c]), Reverse(*c)))
+            .collect()
+    }
+}
+
+impl Ruleset {
+    /// Returns the card with the given strength.
+    fn card(&self, strength: u8) -> char {
+        self.cards
+            .chars()
+            .nth(self.cards.len() - strength as usize)
+            .expect("Should have a card with that strength")
+    }
+
+    fn format_cards(&self, cards: &[u8]) -> String {
+        cards.iter().map(|&c| self.card(c)).collect()
+    }
+
+    /// The same rules, but the wildcards are just ordinary cards.
+    fn without_wildcards(&self) -> Ruleset {
+        Ruleset {
+            wildcards: "",
+            ..self.clone()
+        }
+    }
+
+    /// Returns the hand with each wildcard replaced by the card that makes the best hand,
+    /// which is what `hand_type` assumes: the most common other card, the strongest if
+    /// there's a tie, or the strongest card of all if every card is wild.
+    fn substitute(&self, hand: &Hand) -> Hand {
+        let mut counts = vec![0; self.cards.len() + 1];
+        for &c in hand.cards.iter().filter(|&&c| !self.is_wildcard(c)) {
+            counts[c as usize] += 1;
+        }
+
+        let best = (1..=self.cards.len() as u8)
+            .filter(|&c| !self.is_wildcard(c))
+            .max_by_key(|&c| (counts[c as usize], c))
+            .expect("Should have a card that isn't wild");
+
+        Hand {
+            cards: hand
+                .cards
+                .iter()
+                .map(|&c| if self.is_wildcard(c) { best } else { c })
+                .collect(),
+            suits: hand.suits.clone(),
+            bid: hand.bid,
+        }
+    }
+
+    /// Returns the best hand type from trying every card that isn't wild in place of each
+    /// wildcard, which is slow but doesn't rely on the shortcut `hand_type` takes.
+    fn best_substitution(&self, hand: &Hand) -> HandType {
+        let plain = self.without_wildcards();
+        let wildcards = (0..hand.cards.len())
+            .filter(|&i| self.is_wildcard(hand.cards[i]))
             .collect::<Vec<_>>();
+        if wildcards.is_empty() {
+            return plain.hand_type(hand);
+        }
 
-        if counts.len() <= 1 || counts[0] + jokers == 5 {
-            HandType::FiveOfAKind
-        } else if counts[0] + jokers == 4 {
-            HandType::FourOfAKind
-        } else if ((counts[0] + jokers == 3) && (counts[1] == 2))
-            || ((counts[0] == 3) && (counts[1] + jokers == 2))
-        {
-            HandType::FullHouse
-        } else if counts[0] + jokers == 3 {
-            HandType::ThreeOfAKind
-        } else if (counts[0] + jokers == 2 && counts[1] == 2)
-            || (counts[0] == 2 && counts[1] + jokers == 2)
-        {
-            HandType::TwoPair
-        } else if counts[0] + jokers == 2 {
-            HandType::OnePair
-        } else {
-            HandType::HighCard
+        let replacements = (1..=self.cards.len() as u8).filter(|&c| !self.is_wildcard(c));
+        wildcards
+            .iter()
+            .map(|_| replacements.clone())
+            .multi_cartesian_product()
+            .map(|replacement| {
+                let mut substituted = hand.clone();
+                for (&i, card) in zip(&wildcards, replacement) {
+                    substituted.cards[i] = card;
+                }
+                plain.hand_type(&substituted)
+            })
+            .min()
+            .expect("Should have tried at least one replacement")
+    }
+
+    /// Returns every hand that can be dealt, with a bid of 1. Without suits every sequence
+    /// of cards is a different hand, since ties are broken card by card. With suits each
+    /// card comes from the deck once, and the order it was dealt in doesn't matter.
+    fn all_hands(&self) -> Box<dyn Iterator<Item = Hand> + '_> {
+        let strengths = 1..=self.cards.len() as u8;
+
+        if self.suits.is_empty() {
+            return Box::new(
+                (0..self.hand_size)
+                    .map(|_| strengths.clone())
+                    .multi_cartesian_product()
+                    .map(|cards| Hand {
+                        cards,
+                        suits: Vec::new(),
+                        bid: 1,
+                    }),
+            );
         }
+
+        let deck = strengths
+            .cartesian_product(self.suits.chars())
+            .collect::<Vec<_>>();
+        Box::new(deck.into_iter().combinations(self.hand_size).map(|dealt| {
+            let (cards, suits) = dealt.into_iter().unzip();
+            Hand {
+                cards,
+                suits,
+                bid: 1,
+            }
+        }))
+    }
+
+    /// Packs the hand type and the strength of each card into one number that sorts
+    /// weakest hand first: the hand type in the top bits, then each card in tiebreak order.
+    fn sort_key(&self, hand: &Hand) -> u64 {
+        // enough bits for the strongest card
+        let bits = u64::BITS - (self.cards.len() as u64).leading_zeros();
+        assert!(
+            bits as usize * self.hand_size + u8::BITS as usize <= u64::BITS as usize,
+            "Should have few enough cards to fit a sort key in a u64"
+        );
+
+        // hand types go strongest first, so flip them over
+        let hand_type = (u8::MAX - self.hand_type(hand) as u8) as u64;
+        self.tiebreak(hand)
+            .iter()
+            .fold(hand_type, |key, &card| key << bits | card as u64)
     }
 }
 
-fn parse(input: &str, mappings: &'static str) -> Vec<Hand> {
+fn parse(input: &str, rules: &Ruleset) -> Vec<Hand> {
+    assert!(
+        rules.suits.is_empty(),
+        "Should parse hands with suits with parse_poker"
+    );
     let mut hands = Vec::new();
 
     for line in input.lines() {
-        let (cards, bid) = line.split_at(5);
+        let (cards, bid) = line
+            .split_once(' ')
+            .expect("Should have found cards and a bid");
+        assert_eq!(
+            cards.chars().count(),
+            rules.hand_size,
+            "Should have {} cards in {}",
+            rules.hand_size,
+            cards
+        );
 
-        let cards = cards
-            .as_bytes()
-            .iter()
-            .map(|&c| 13 - (mappings.find(c as char).unwrap() as u8))
-            .collect();
+        let cards = cards.chars().map(|c| rules.strength(c)).collect();
 
         let bid = bid.trim().parse().unwrap();
 
-        hands.push(Hand { cards, bid });
+        hands.push(Hand {
+            cards,
+            suits: Vec::new(),
+            bid,
+        });
+    }
+    hands
+}
+
+/// Parses poker hands, where each card is its rank then its suit, e.g. `AS KD QH JC TS 765`.
+fn parse_poker(input: &str, rules: &Ruleset) -> Vec<Hand> {
+    let mut hands = Vec::new();
+
+    for line in input.lines() {
+        let mut tokens = line.split_whitespace().collect::<Vec<_>>();
+        let bid = tokens
+            .pop()
+            .expect("Should have found a bid")
+            .parse()
+            .unwrap();
+        assert_eq!(
+            tokens.len(),
+            rules.hand_size,
+            "Should have {} cards in {}",
+            rules.hand_size,
+            line
+        );
+        assert!(
+            tokens.iter().all_unique(),
+            "Should only have one of each card in {}",
+            line
+        );
+
+        let (cards, suits) = tokens
+            .iter()
+            .map(|token| {
+                let mut chars = token.chars();
+                let (Some(card), Some(suit), None) = (chars.next(), chars.next(), chars.next())
+                else {
+                    panic!("Should have a rank and a suit in {}", token);
+                };
+                assert!(rules.suits.contains(suit), "{} is not a suit", suit);
+                (rules.strength(card), suit)
+            })
+            .unzip();
+
+        hands.push(Hand { cards, suits, bid });
     }
     hands
 }
 
-fn solve(mut hands: Vec<Hand>, score: fn(&Hand) -> HandType) -> usize {
-    hands.sort_by(|a, b| score(a).cmp(&score(b)).then_with(|| b.score_first(a)));
+/// Returns the hands in order of rank, weakest first, along with their ranks. Hands that
+/// tie all get the lowest of their ranks, so they win the same for the same bid. With cards
+/// compared in order only identical hands tie, but in poker the suits don't break ties.
+fn rank(hands: Vec<Hand>, rules: &Ruleset) -> Vec<(usize, Hand)> {
+    // work out each hand's sort key once, instead of every time two hands are compared
+    let mut keyed = hands
+        .into_iter()
+        .map(|hand| (rules.sort_key(&hand), hand))
+        .collect::<Vec<_>>();
+    keyed.sort_unstable_by_key(|(key, _)| *key);
+
+    let mut ranked = Vec::with_capacity(keyed.len());
+    let mut previous = None;
+    for (i, (key, hand)) in keyed.into_iter().enumerate() {
+        let rank = match previous {
+            Some((previous_key, previous_rank)) if previous_key == key => previous_rank,
+            _ => i + 1,
+        };
+        previous = Some((key, rank));
+        ranked.push((rank, hand));
+    }
+    ranked
+}
+
+fn solve(hands: Vec<Hand>, rules: &Ruleset) -> usize {
+    let solved = rank(hands, rules)
+        .iter()
+        .map(|(rank, h)| h.bid as usize * rank)
+        .sum::<usize>();
+
+    solved
+}
+
+// what the jokers did for a hand, and what it won
+struct JokerExplanation {
+    cards: String,
+    // the hand with the jokers replaced by what they became
+    best: String,
+    without_jokers: HandType,
+    with_jokers: HandType,
+    rank: usize,
+    bid: u32,
+    winnings: usize,
+}
+
+/// Explains every hand under the part 2 rules, weakest first.
+fn explain_jokers(input: &str) -> Vec<JokerExplanation> {
+    let rules = Ruleset::part_2();
+    let plain = rules.without_wildcards();
+
+    rank(parse(input, &rules), &rules)
+        .iter()
+        .map(|&(rank, ref hand)| {
+            let best = rules.substitute(hand);
+            JokerExplanation {
+                cards: rules.format_cards(&hand.cards),
+                best: rules.format_cards(&best.cards),
+                without_jokers: plain.hand_type(hand),
+                with_jokers: rules.hand_type(hand),
+                rank,
+                bid: hand.bid,
+                winnings: hand.bid as usize * rank,
+            }
+        })
+        .collect()
+}
+
+fn explain(format: &str) {
+    let input = include_str!("my_input.txt");
+    let explanations = explain_jokers(input);
+
+    match format {
+        "table" => {
+            println!(
+                "{:<6} {:<6} {:<14} {:<14} {:>6} {:>6} {:>10}",
+                "Hand", "Best", "Without Jokers", "With Jokers", "Rank", "Bid", "Winnings"
+            );
+            for e in &explanations {
+                println!(
+                    "{:<6} {:<6} {:<14} {:<14} {:>6} {:>6} {:>10}",
+                    e.cards,
+                    e.best,
+                    format!("{:?}", e.without_jokers),
+                    format!("{:?}", e.with_jokers),
+                    e.rank,
+                    e.bid,
+                    e.winnings
+                );
+            }
+        }
+        "csv" => {
+            println!("hand,best,without_jokers,with_jokers,rank,bid,winnings");
+            for e in &explanations {
+                println!(
+                    "{},{},{:?},{:?},{},{},{}",
+                    e.cards, e.best, e.without_jokers, e.with_jokers, e.rank, e.bid, e.winnings
+                );
+            }
+        }
+        _ => panic!("unknown explain format: {}", format),
+    }
+}
+
+// how likely a hand type is, and how well a hand of that type does
+#[derive(Debug)]
+struct HandTypeOdds {
+    hand_type: HandType,
+    // how many of all the hands that can be dealt are this type
+    hands: usize,
+    probability: f64,
+    // the average rank of a hand of this type in a game of randomly dealt hands
+    expected_rank: f64,
+}
+
+/// Deals every possible hand under the rules, and works out how often each hand type comes
+/// up and what rank a hand of each type can expect in a game of `game` hands, each dealt at
+/// random from the rest.
+fn hand_type_odds(rules: &Ruleset, game: usize) -> Vec<HandTypeOdds> {
+    let mut keyed = rules
+        .all_hands()
+        .map(|hand| (rules.sort_key(&hand), rules.hand_type(&hand)))
+        .collect::<Vec<_>>();
+    keyed.sort_unstable();
+    let total = keyed.len();
+
+    // for each hand type, how many hands and the sum of the ranks they can expect
+    let mut odds = BTreeMap::<HandType, (usize, f64)>::new();
+    let mut weaker = 0;
+    for run in keyed.chunk_by(|a, b| a.0 == b.0) {
+        // like `rank`, a hand's rank is one more than the number of hands it beats,
+        // so hands that tie share a rank
+        let expected_rank = 1.0 + (game - 1) as f64 * weaker as f64 / (total - 1) as f64;
+        for &(_, hand_type) in run {
+            let (hands, ranks) = odds.entry(hand_type).or_default();
+            *hands += 1;
+            *ranks += expected_rank;
+        }
+        weaker += run.len();
+    }
+
+    odds.into_iter()
+        .map(|(hand_type, (hands, ranks))| HandTypeOdds {
+            hand_type,
+            hands,
+            probability: hands as f64 / total as f64,
+            expected_rank: ranks / hands as f64,
+        })
+        .collect()
+}
+
+/// Returns every hand whose type isn't the one found by trying each card in place of
+/// its wildcards.
+fn substitution_mismatches(rules: &Ruleset) -> Vec<Hand> {
+    rules
+        .all_hands()
+        .filter(|hand| rules.hand_type(hand) != rules.best_substitution(hand))
+        .collect()
+}
+
+/// Prints the odds of each hand type, and what a hand of each type can expect to win with
+/// bids spread evenly over `bids`. With wildcards, it also checks every hand's type against
+/// trying each card in place of the wildcards.
+fn probabilities(rules: &Ruleset, game: usize, bids: std::ops::RangeInclusive<u32>) {
+    assert!(game >= 2, "Should have at least two hands in a game");
+    let mean_bid = (*bids.start() as f64 + *bids.end() as f64) / 2.0;
+
+    let odds = hand_type_odds(rules, game);
+    println!(
+        "{} hands dealt from {}{}, {} in a game, bids from {} to {}",
+        odds.iter().map(|odds| odds.hands).sum::<usize>(),
+        rules.cards,
+        if rules.wildcards.is_empty() {
+            String::new()
+        } else {
+            format!(" with {} wild", rules.wildcards)
+        },
+        game,
+        bids.start(),
+        bids.end()
+    );
+    println!(
+        "{:<14} {:>8} {:>12} {:>14} {:>14}",
+        "Hand Type", "Hands", "Probability", "Expected Rank", "Expected Win"
+    );
+    for odds in &odds {
+        println!(
+            "{:<14} {:>8} {:>12.6} {:>14.2} {:>14.2}",
+            format!("{:?}", odds.hand_type),
+            odds.hands,
+            odds.probability,
+            odds.expected_rank,
+            odds.expected_rank * mean_bid
+        );
+    }
+
+    if !rules.wildcards.is_empty() {
+        let mismatches = substitution_mismatches(rules);
+        println!(
+            "Hand types by substitution: {} hands differ",
+            mismatches.len()
+        );
+        for hand in mismatches.iter().take(10) {
+            println!(
+                "  {}: {:?}, but {:?} by substitution",
+                rules.format_cards(&hand.cards),
+                rules.hand_type(hand),
+                rules.best_substitution(hand)
+            );
+        }
+    }
+}
+
+/// Same as `solve`, but works out the hand types every time two hands are compared.
+/// Kept to benchmark against.
+fn solve_by_comparing(mut hands: Vec<Hand>, rules: &Ruleset) -> usize {
+    hands.sort_by(|a, b| {
+        rules
+            .hand_type(a)
+            .cmp(&rules.hand_type(b))
+            .then_with(|| b.score_first(a))
+    });
 
     let solved = hands
         .iter()
@@ -119,11 +681,83 @@ fn solve(mut hands: Vec<Hand>, score: fn(&Hand) -> HandType) -> usize {
     solved
 }
 
+/// Same as `solve_by_comparing`, but works out each hand's type once before sorting.
+/// Kept to benchmark against, to tell how much of the difference is the classifier.
+fn solve_by_cached_types(hands: Vec<Hand>, rules: &Ruleset) -> usize {
+    let mut typed = hands
+        .into_iter()
+        .map(|hand| (rules.hand_type(&hand), hand))
+        .collect::<Vec<_>>();
+    typed.sort_by(|(a_type, a), (b_type, b)| a_type.cmp(b_type).then_with(|| b.score_first(a)));
+
+    typed
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, (_, h))| h.bid as usize * (i + 1))
+        .sum()
+}
+
+/// Times each way of solving on lots of random hands.
+fn bench() {
+    use std::time::Instant;
+
+    const HANDS: usize = 100_000;
+
+    // a small xorshift generator, so the hands are the same every run
+    let mut state = 0x2545F4914F6CDD1Du64;
+    let mut random = move |below: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize % below
+    };
+
+    for (name, rules) in [("Part 1", Ruleset::part_1()), ("Part 2", Ruleset::part_2())] {
+        // no two hands are the same, like the puzzle, so there's only one right order
+        let cards = rules.cards.as_bytes();
+        let mut seen = HashSet::new();
+        let mut input = Vec::new();
+        while input.len() < HANDS {
+            let hand = (0..rules.hand_size)
+                .map(|_| cards[random(cards.len())] as char)
+                .collect::<String>();
+            if seen.insert(hand.clone()) {
+                input.push(format!("{} {}", hand, random(1000) + 1));
+            }
+        }
+        let input = input.join("\n");
+
+        let hands = parse(&input, &rules);
+        let start = Instant::now();
+        let compared = solve_by_comparing(hands, &rules);
+        let comparing_time = start.elapsed();
+
+        let hands = parse(&input, &rules);
+        let start = Instant::now();
+        let typed = solve_by_cached_types(hands, &rules);
+        let typed_time = start.elapsed();
+
+        let hands = parse(&input, &rules);
+        let start = Instant::now();
+        let keyed = solve(hands, &rules);
+        let keyed_time = start.elapsed();
+
+        assert_eq!(compared, keyed);
+        assert_eq!(typed, keyed);
+        println!("{} with {} hands:", name, HANDS);
+        println!("  Comparing hand types: {:?}", comparing_time);
+        println!("  Cached hand types:    {:?}", typed_time);
+        println!("  Cached sort keys:     {:?}", keyed_time);
+    }
+}
+
 fn part_1() {
     let input = include_str!("my_input.txt");
 
-    let hands = parse(input, "AKQJT98765432");
-    let solved = solve(hands, Hand::score_part_1);
+    let rules = Ruleset::part_1();
+    let hands = parse(input, &rules);
+    let solved = solve(hands, &rules);
 
     println!("Part 1: {}", solved);
 }
@@ -131,13 +765,397 @@ fn part_1() {
 fn part_2() {
     let input = include_str!("my_input.txt");
 
-    let hands = parse(input, "AKQT98765432J");
-    let solved = solve(hands, Hand::score_part_2);
+    let rules = Ruleset::part_2();
+    let hands = parse(input, &rules);
+    let solved = solve(hands, &rules);
 
     println!("Part 2: {}", solved);
 }
 
+fn poker() {
+    let input = include_str!("poker_input.txt");
+
+    let rules = Ruleset::poker();
+    let hands = parse_poker(input, &rules);
+
+    for (rank, hand) in rank(hands.clone(), &rules) {
+        let cards = zip(&hand.cards, &hand.suits)
+            .map(|(&card, suit)| format!("{}{}", rules.card(card), suit))
+            .join(" ");
+        println!("{:>3}  {}  {:?}", rank, cards, rules.hand_type(&hand));
+    }
+    println!("Poker: {}", solve(hands, &rules));
+}
+
+// usage: day07 [--bench] [--explain table|csv] [--poker]
+//              [--probabilities part1|part2|poker [GAME_SIZE [MIN_BID..MAX_BID]]]
 fn main() {
+    let mut args = std::env::args().skip(1);
+
+    if let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bench" => return bench(),
+            "--poker" => return poker(),
+            "--probabilities" => {
+                let rules = match args.next().as_deref() {
+                    Some("part1") => Ruleset::part_1(),
+                    Some("part2") => Ruleset::part_2(),
+                    Some("poker") => Ruleset::poker(),
+                    other => panic!("unknown ruleset: {:?}", other),
+                };
+                // the puzzle has 1000 hands, and its bids look like they're up to 1000
+                let game = args.next().map_or(1000, |game| game.parse().unwrap());
+                let bids = args.next().map_or(1..=1000, |bids| {
+                    let (min, max) = bids
+                        .split_once("..")
+                        .expect("Should have bids like MIN..MAX");
+                    min.parse().unwrap()..=max.parse().unwrap()
+                });
+                return probabilities(&rules, game, bids);
+            }
+            "--explain" => {
+                let format = args.next().expect("Should have a format after --explain");
+                return explain(&format);
+            }
+            _ => panic!("unknown argument: {}", arg),
+        }
+    }
+
     part_1();
     part_2();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("input.txt");
+
+    fn hand_type(rules: &Ruleset, cards: &str) -> HandType {
+        rules.hand_type(&parse(&format!("{} 1", cards), rules)[0])
+    }
+
+    #[test]
+    fn sample_part_1() {
+        let rules = Ruleset::part_1();
+        assert_eq!(solve(parse(SAMPLE, &rules), &rules), 6440);
+    }
+
+    #[test]
+    fn sample_part_2() {
+        let rules = Ruleset::part_2();
+        assert_eq!(solve(parse(SAMPLE, &rules), &rules), 5905);
+    }
+
+    #[test]
+    fn explain_sample_jokers() {
+        let explanations = explain_jokers(SAMPLE);
+        let explained = explanations
+            .iter()
+            .map(|e| {
+                (
+                    e.cards.as_str(),
+                    e.best.as_str(),
+                    e.without_jokers,
+                    e.with_jokers,
+                    e.rank,
+                    e.winnings,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            explained,
+            [
+                (
+                    "32T3K",
+                    "32T3K",
+                    HandType::OnePair,
+                    HandType::OnePair,
+                    1,
+                    765
+                ),
+                (
+                    "KK677",
+                    "KK677",
+                    HandType::TwoPair,
+                    HandType::TwoPair,
+                    2,
+                    56
+                ),
+                (
+                    "T55J5",
+                    "T5555",
+                    HandType::ThreeOfAKind,
+                    HandType::FourOfAKind,
+                    3,
+                    2052
+                ),
+                (
+                    "QQQJA",
+                    "QQQQA",
+                    HandType::ThreeOfAKind,
+                    HandType::FourOfAKind,
+                    4,
+                    1932
+                ),
+                (
+                    "KTJJT",
+                    "KTTTT",
+                    HandType::TwoPair,
+                    HandType::FourOfAKind,
+                    5,
+                    1100
+                ),
+            ]
+        );
+
+        // the winnings add up to the same total as solving part 2
+        let winnings = explanations.iter().map(|e| e.winnings).sum::<usize>();
+        assert_eq!(winnings, 5905);
+        assert_eq!(
+            winnings,
+            solve(parse(SAMPLE, &Ruleset::part_2()), &Ruleset::part_2())
+        );
+    }
+
+    #[test]
+    fn camel_hand_types() {
+        let rules = Ruleset::part_1();
+        assert_eq!(hand_type(&rules, "AAAAA"), HandType::FiveOfAKind);
+        assert_eq!(hand_type(&rules, "AA8AA"), HandType::FourOfAKind);
+        assert_eq!(hand_type(&rules, "23332"), HandType::FullHouse);
+        assert_eq!(hand_type(&rules, "TTT98"), HandType::ThreeOfAKind);
+        assert_eq!(hand_type(&rules, "23432"), HandType::TwoPair);
+        assert_eq!(hand_type(&rules, "A23A4"), HandType::OnePair);
+        assert_eq!(hand_type(&rules, "23456"), HandType::HighCard);
+        // no straights in Camel Cards
+        assert_eq!(hand_type(&rules, "T9876"), HandType::HighCard);
+    }
+
+    #[test]
+    fn jokers_join_the_biggest_group() {
+        let rules = Ruleset::part_2();
+        assert_eq!(hand_type(&rules, "JJJJJ"), HandType::FiveOfAKind);
+        assert_eq!(hand_type(&rules, "QJJQ2"), HandType::FourOfAKind);
+        assert_eq!(hand_type(&rules, "KKJ22"), HandType::FullHouse);
+        assert_eq!(hand_type(&rules, "T55J5"), HandType::FourOfAKind);
+        assert_eq!(hand_type(&rules, "2345J"), HandType::OnePair);
+        // without jokers, J is just a jack
+        assert_eq!(hand_type(&Ruleset::part_1(), "QJJQ2"), HandType::TwoPair);
+    }
+
+    #[test]
+    fn jokers_are_the_weakest_card() {
+        let rules = Ruleset::part_2();
+        assert!(rules.strength('J') < rules.strength('2'));
+        assert!(Ruleset::part_1().strength('J') > Ruleset::part_1().strength('T'));
+
+        // JKKK2 is weaker than QQQQ2 even though both are four of a kind
+        let hands = parse("QQQQ2 1\nJKKK2 2", &rules);
+        let ranked = rank(hands, &rules);
+        assert_eq!(rules.format_cards(&ranked[0].1.cards), "JKKK2");
+    }
+
+    fn poker_hand(cards: &str) -> Hand {
+        parse_poker(&format!("{} 1", cards), &Ruleset::poker()).remove(0)
+    }
+
+    fn poker_type(cards: &str) -> HandType {
+        Ruleset::poker().hand_type(&poker_hand(cards))
+    }
+
+    /// Returns the ranks of the hands, in the order they're given.
+    fn poker_ranks(hands: &[&str]) -> Vec<usize> {
+        let rules = Ruleset::poker();
+        let input = hands
+            .iter()
+            .enumerate()
+            .map(|(i, cards)| format!("{} {}", cards, i))
+            .join("\n");
+        rank(parse_poker(&input, &rules), &rules)
+            .into_iter()
+            .sorted_by_key(|(_, hand)| hand.bid)
+            .map(|(rank, _)| rank)
+            .collect()
+    }
+
+    #[test]
+    fn poker_hand_types() {
+        assert_eq!(poker_type("TH JH QH KH AH"), HandType::StraightFlush);
+        assert_eq!(poker_type("AD AC AS AH 2C"), HandType::FourOfAKind);
+        assert_eq!(poker_type("2H 4S 4C 2D 4H"), HandType::FullHouse);
+        assert_eq!(poker_type("2S 8S AS QS 3S"), HandType::Flush);
+        assert_eq!(poker_type("9S TD JC QS KH"), HandType::Straight);
+        assert_eq!(poker_type("3C 3D 3S 9H KC"), HandType::ThreeOfAKind);
+        assert_eq!(poker_type("KS KD 7C 7H 2S"), HandType::TwoPair);
+        assert_eq!(poker_type("QC QD 5H 8S 2D"), HandType::OnePair);
+        assert_eq!(poker_type("2H 3D 5S 9C KD"), HandType::HighCard);
+    }
+
+    #[test]
+    fn wheel_is_a_five_high_straight() {
+        assert_eq!(poker_type("AH 2D 3C 4S 5H"), HandType::Straight);
+        assert_eq!(poker_type("AH 2H 3H 4H 5H"), HandType::StraightFlush);
+        let rules = Ruleset::poker();
+        assert_eq!(
+            rules.tiebreak(&poker_hand("AH 2D 3C 4S 5H")),
+            [4, 3, 2, 1, 0]
+        );
+
+        // straights don't wrap around past the ace
+        assert_eq!(poker_type("KH AD 2C 3S 4H"), HandType::HighCard);
+        assert_eq!(poker_type("KH AH 2H 3H 4H"), HandType::Flush);
+
+        // the wheel is the weakest straight
+        assert_eq!(
+            poker_ranks(&["6D 2C 3H 4S 5H", "AH 2D 3C 4S 5H", "TH JD QC KS AS"]),
+            [2, 1, 3]
+        );
+    }
+
+    #[test]
+    fn poker_ties_by_group() {
+        // the pairs are the same, so the kicker decides
+        assert_eq!(poker_ranks(&["KS KD 7C 7H 3S", "KC KH 7S 7D 2D"]), [2, 1]);
+        // a higher pair beats higher kickers
+        assert_eq!(poker_ranks(&["2S 2D AC KH QS", "3C 3H 4S 5D 7D"]), [1, 2]);
+        // where the cards are in the hand doesn't matter
+        assert_eq!(poker_ranks(&["AS 2D 2C 3H 4S", "2S 2H 5C 4D 3D"]), [2, 1]);
+    }
+
+    #[test]
+    fn poker_hands_that_only_differ_by_suit_tie() {
+        let hands = [
+            "9H 9D 4S 5C 7D",
+            "KS KD 7C 7H 3S",
+            "KC KH 7S 7D 3D",
+            "AH AC 2S 3H 4D",
+            "2S 2D 2H 3C 3D",
+        ];
+        // the two pairs share rank 3, and nothing gets rank 4
+        assert_eq!(poker_ranks(&hands), [1, 3, 3, 2, 5]);
+
+        // so they win the same for the same bid
+        let rules = Ruleset::poker();
+        let hands = parse_poker("KS KD 7C 7H 3S 10\nKC KH 7S 7D 3D 10", &rules);
+        assert_eq!(solve(hands, &rules), 10 + 10);
+    }
+
+    #[test]
+    fn identical_camel_hands_tie() {
+        let rules = Ruleset::part_1();
+        let hands = parse("32T3K 10\n32T3K 20\nKK677 5", &rules);
+        assert_eq!(solve(hands, &rules), 10 + 20 + 5 * 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Should have a suit for every card")]
+    fn poker_rules_need_suits() {
+        let rules = Ruleset::poker();
+        let hand = Hand {
+            cards: vec![13, 12, 11, 10, 5],
+            suits: Vec::new(),
+            bid: 1,
+        };
+        rules.hand_type(&hand);
+    }
+
+    #[test]
+    #[should_panic(expected = "parse_poker")]
+    fn parse_refuses_rules_with_suits() {
+        parse("AKQJ9 1", &Ruleset::poker());
+    }
+
+    #[test]
+    fn jokers_match_every_replacement() {
+        // the shortcut picks a card for the jokers without trying them all, so it can be
+        // checked against the slow way on all 13^5 hands
+        let rules = Ruleset::part_2();
+        let mut hands = 0;
+        for hand in rules.all_hands() {
+            assert_eq!(
+                rules.hand_type(&hand),
+                rules.best_substitution(&hand),
+                "Should have the best hand type for {}",
+                rules.format_cards(&hand.cards)
+            );
+
+            // the jokers become the card the shortcut picks, and that hand is just as good
+            let substituted = rules.substitute(&hand);
+            assert_eq!(
+                rules.without_wildcards().hand_type(&substituted),
+                rules.hand_type(&hand),
+                "Should have substituted the best card into {}",
+                rules.format_cards(&hand.cards)
+            );
+            hands += 1;
+        }
+        assert_eq!(hands, 13usize.pow(5));
+    }
+
+    #[test]
+    fn camel_hand_type_odds() {
+        let odds = hand_type_odds(&Ruleset::part_1(), 1000);
+        let hands = odds
+            .iter()
+            .map(|odds| (odds.hand_type, odds.hands))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hands,
+            [
+                (HandType::FiveOfAKind, 13),
+                (HandType::FourOfAKind, 13 * 12 * 5),
+                (HandType::FullHouse, 13 * 12 * 10),
+                (HandType::ThreeOfAKind, 13 * 66 * 20),
+                (HandType::TwoPair, 78 * 11 * 30),
+                (HandType::OnePair, 13 * 220 * 60),
+                (HandType::HighCard, 13 * 12 * 11 * 10 * 9),
+            ]
+        );
+        assert_eq!(
+            odds.iter().map(|odds| odds.hands).sum::<usize>(),
+            13usize.pow(5)
+        );
+
+        // stronger hand types rank higher, and a game's ranks average out to the middle
+        assert!(odds
+            .windows(2)
+            .all(|w| w[0].expected_rank > w[1].expected_rank));
+        let mean_rank = odds
+            .iter()
+            .map(|odds| odds.probability * odds.expected_rank)
+            .sum::<f64>();
+        assert!((mean_rank - 500.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn joker_hand_type_odds() {
+        let odds = hand_type_odds(&Ruleset::part_2(), 2);
+        // five jokers, or four, or three with a pair, or two with three of a kind,
+        // or one with four of a kind, or none with five of a kind
+        let five_of_a_kind = &odds[0];
+        assert_eq!(five_of_a_kind.hand_type, HandType::FiveOfAKind);
+        assert_eq!(
+            five_of_a_kind.hands,
+            1 + 5 * 12 + 10 * 12 + 10 * 12 + 5 * 12 + 12
+        );
+        assert!(odds.iter().all(|odds| odds.hand_type != HandType::Straight));
+    }
+
+    #[test]
+    fn hands_that_tie_share_an_expected_rank() {
+        // with only five cards the one straight flush comes in four suits, and those
+        // four hands tie, so each beats every hand but the other three
+        let rules = Ruleset {
+            cards: "AKQJT",
+            ..Ruleset::poker()
+        };
+        let total = rules.all_hands().count();
+        let odds = hand_type_odds(&rules, 2);
+        assert_eq!(odds[0].hand_type, HandType::StraightFlush);
+        assert_eq!(odds[0].hands, 4);
+
+        let expected_rank = 1.0 + (total - 4) as f64 / (total - 1) as f64;
+        assert!((odds[0].expected_rank - expected_rank).abs() < 1e-12);
+    }
+}